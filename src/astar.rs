@@ -0,0 +1,129 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use crate::{
+    search::{Budget, Limit},
+    store::Store,
+    Game, Heuristic, Solution,
+};
+
+/// Searches best first, ordering games by moves made plus the estimate of
+/// moves left. Both built-in heuristics drop by at most one per move, so the
+/// first solved game taken off the heap has a shortest solution.
+pub(crate) fn search(
+    game: &Game,
+    heuristic: &dyn Heuristic,
+    budget: &mut Budget,
+) -> Result<Option<Solution>, Limit> {
+    let mut games = Store::new();
+    let mut open = BinaryHeap::new();
+    let start = games.insert(game, None).expect("Store starts empty");
+    // Ties go to the deeper game, which is usually closer to a solution
+    open.push((Reverse(heuristic.estimate(game)), 0, start));
+
+    let mut bound = heuristic.estimate(game);
+    while let Some((Reverse(total), depth, index)) = open.pop() {
+        if total > bound {
+            budget.progress(bound, open.len() + 1, games.len());
+            bound = total;
+        }
+        if !games.is_latest(index) {
+            continue; // Already reached this game in fewer moves
+        }
+        let game = games.game(index);
+        if game.is_solved() {
+            let solution = games.path(index);
+            budget.found(&solution);
+            return Ok(Some(solution));
+        }
+        budget.expand(depth)?;
+        if budget.too_deep(depth + 1) {
+            continue;
+        }
+        for (step, child) in budget.children(&game) {
+            let depth = depth + 1;
+            let estimate = heuristic.estimate(&child);
+            if let Some(known) = games.find(&child) {
+                if games.depth(known) <= depth {
+                    continue;
+                }
+            }
+            let child = games.push(&child, Some((index, step)));
+            open.push((Reverse(depth + estimate), depth, child));
+        }
+        budget.sizes(open.len(), games.len());
+    }
+    budget.exhausted()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Boundaries, ExtraBlocks, Limits, SolveOutcome, Solver};
+
+    fn search(game: &Game, heuristic: &dyn Heuristic) -> Option<Solution> {
+        super::search(game, heuristic, &mut Budget::new(&Limits::default())).unwrap()
+    }
+
+    #[test]
+    fn finds_shortest_solution() {
+        let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]);
+        assert_eq!(search(&game, &Boundaries).unwrap().len(), 7);
+        assert_eq!(search(&game, &ExtraBlocks).unwrap().len(), 7);
+    }
+
+    #[test]
+    fn finds_shortest_solution_with_mixed_capacities() {
+        for game in [
+            Game::mixed(vec![
+                vec![1, 2, 1, 2],
+                vec![2, 1, 2, 1],
+                vec![0; 2],
+                vec![0; 2],
+            ]),
+            Game::mixed(vec![
+                vec![2, 1, 1, 2],
+                vec![1, 2, 2, 1],
+                vec![0; 2],
+                vec![0; 2],
+            ]),
+            Game::mixed(vec![vec![1, 2, 2, 2], vec![2, 1], vec![1, 1], vec![0; 4]]),
+        ] {
+            let game = game.unwrap();
+            let shortest = match Solver::new(game.clone()).unwrap().solve() {
+                SolveOutcome::Solved { depth, .. } => depth,
+                outcome => panic!("Expected a solution, got {outcome:?}"),
+            };
+            assert_eq!(search(&game, &Boundaries).unwrap().len(), shortest);
+            assert_eq!(search(&game, &ExtraBlocks).unwrap().len(), shortest);
+        }
+    }
+
+    #[test]
+    fn solved_game_needs_no_moves() {
+        let game = Game::new(vec![[1; 4], [0; 4]]);
+        assert_eq!(search(&game, &ExtraBlocks), Some(vec![]));
+    }
+
+    #[test]
+    fn unsolvable_game() {
+        let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1]]);
+        assert_eq!(search(&game, &ExtraBlocks), None);
+    }
+
+    #[test]
+    fn depth_limit() {
+        let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]);
+        let limits = Limits {
+            max_depth: Some(6),
+            ..Limits::default()
+        };
+        let aborted = super::search(&game, &ExtraBlocks, &mut Budget::new(&limits));
+        assert_eq!(aborted, Err(Limit::Depth));
+        let limits = Limits {
+            max_depth: Some(7),
+            ..Limits::default()
+        };
+        let solution = super::search(&game, &ExtraBlocks, &mut Budget::new(&limits)).unwrap();
+        assert_eq!(solution.unwrap().len(), 7);
+    }
+}
@@ -0,0 +1,369 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    slice,
+};
+
+use crate::{
+    search::{Budget, Expansion, Limit},
+    store::Store,
+    Color, Game, Pruned, Pruning, Solution, Tube, MAX_CAPACITY,
+};
+
+/// Searches breadth first from the start and backward from every solved
+/// arrangement of its colors at once, a layer at a time on whichever side
+/// looks set to add fewer games, until a game turns up on both sides.
+///
+/// Every game seen on one side within `d` moves of its roots is known when
+/// the other side grows, so the first layer to reach a game of the other
+/// side can only do so through the shortest solutions. The search stops at
+/// the first one.
+pub(crate) fn search(game: &Game, budget: &mut Budget) -> Result<Option<Solution>, Limit> {
+    // The goals are sorted, so a solved start need not be among them
+    if game.is_solved() {
+        let solution = vec![];
+        budget.found(&solution);
+        return Ok(Some(solution));
+    }
+    let mut forward = Side::new(slice::from_ref(game), true);
+    let mut backward = Side::new(&goals(game, budget.expansion.symmetry), false);
+
+    loop {
+        // The length of any solution found growing either side
+        let depth = forward.depth + backward.depth + 1;
+        if budget.too_deep(depth) {
+            return Err(Limit::Depth);
+        }
+        let met = if forward.next_layer() <= backward.next_layer() {
+            forward.grow(&backward, budget, |budget, game| budget.children(game))?
+        } else {
+            let expansion = budget.expansion;
+            backward.grow(&forward, budget, |_, game| {
+                unpours(game, expansion.symmetry)
+            })?
+        };
+        budget.progress(
+            depth,
+            forward.layer.len() + backward.layer.len(),
+            forward.games.len() + backward.games.len(),
+        );
+        match met {
+            Some(Meeting::Forward {
+                parent,
+                step,
+                child,
+                known,
+            }) => {
+                let mut moves = forward.games.path(parent);
+                moves.push(step);
+                return Ok(Some(meet(budget, child, moves, &backward, known)));
+            }
+            Some(Meeting::Backward { known, child }) => {
+                let game = forward.games.game(known);
+                let moves = forward.games.path(known);
+                return Ok(Some(meet(budget, game, moves, &backward, child)));
+            }
+            None if forward.layer.is_empty() || backward.layer.is_empty() => return Ok(None),
+            None => {}
+        }
+    }
+}
+
+/// One direction of the search.
+struct Side {
+    games: Store,
+    /// Indices into `games` of the games `depth` moves from the roots.
+    layer: Vec<usize>,
+    depth: usize,
+    is_forward: bool,
+    /// How many games the last layer grew into per game in it.
+    growth: f64,
+}
+
+/// Where the two sides first reached the same game.
+enum Meeting {
+    /// `child`, reached by `step` from the forward game at `parent`, is the
+    /// backward game at `known`.
+    Forward {
+        parent: usize,
+        step: (usize, usize),
+        child: Game,
+        known: usize,
+    },
+    /// The backward game at `child` is the forward game at `known`.
+    Backward { known: usize, child: usize },
+}
+
+impl Side {
+    fn new(roots: &[Game], is_forward: bool) -> Self {
+        let mut games = Store::new();
+        let layer = roots
+            .iter()
+            .filter_map(|root| games.insert(root, None))
+            .collect();
+        Side {
+            games,
+            layer,
+            depth: 0,
+            is_forward,
+            growth: 1.0,
+        }
+    }
+
+    /// About how many games growing the side would add.
+    fn next_layer(&self) -> f64 {
+        self.layer.len() as f64 * self.growth
+    }
+
+    /// Expands every game in the layer with `children`, storing the games
+    /// not seen before as the next layer, and stops at the first one the
+    /// `other` side has seen.
+    fn grow(
+        &mut self,
+        other: &Side,
+        budget: &mut Budget,
+        mut children: impl FnMut(&mut Budget, &Game) -> Vec<((usize, usize), Game)>,
+    ) -> Result<Option<Meeting>, Limit> {
+        let mut layer = vec![];
+        for &index in &self.layer {
+            budget.expand(self.depth)?;
+            let game = self.games.game(index);
+            for (step, child) in children(budget, &game) {
+                if let Some(known) = other.games.find(&child) {
+                    let meeting = if self.is_forward {
+                        Meeting::Forward {
+                            parent: index,
+                            step,
+                            child,
+                            known,
+                        }
+                    } else {
+                        let child = self.games.push(&child, Some((index, step)));
+                        Meeting::Backward { known, child }
+                    };
+                    return Ok(Some(meeting));
+                }
+                layer.extend(self.games.insert(&child, Some((index, step))));
+            }
+        }
+        self.growth = layer.len() as f64 / self.layer.len() as f64;
+        self.layer = layer;
+        self.depth += 1;
+        Ok(None)
+    }
+}
+
+/// Finishes a solution that has made `moves` to reach `game`, which is the
+/// backward game at `index`, by following the backward links to a solved
+/// game. Equal games can have their tube ids in a different order, so each
+/// move is found again from `game` rather than copied.
+fn meet(
+    budget: &mut Budget,
+    mut game: Game,
+    mut moves: Solution,
+    backward: &Side,
+    mut index: usize,
+) -> Solution {
+    let expansion = Expansion {
+        pruning: Pruning::none(),
+        symmetry: budget.expansion.symmetry,
+    };
+    while let Some((next, _)) = backward.games.parent(index) {
+        let target = backward.games.game(next);
+        let (step, child) = expansion
+            .children(&game, &mut Pruned::default())
+            .into_iter()
+            .find(|(_, child)| *child == target)
+            .expect("Each backward game can be poured into the next");
+        moves.push(step);
+        game = child;
+        index = next;
+    }
+    budget.found(&moves);
+    moves
+}
+
+/// Every solved game with the same tubes and colors as `game`. A color fills
+/// whole tubes, so each way to make up its units from tubes still free is a
+/// separate goal.
+fn goals(game: &Game, symmetry: bool) -> Vec<Game> {
+    let mut counts: BTreeMap<Color, usize> = BTreeMap::new();
+    for color in game.state.iter().flat_map(Tube::colors) {
+        if color.is_value() {
+            *counts.entry(*color).or_default() += 1;
+        }
+    }
+    let mut tubes = game.state.clone();
+    for tube in &mut tubes {
+        tube.colors = [Color::Empty; MAX_CAPACITY];
+    }
+    tubes.sort_by_key(|tube| (tube.capacity, tube.id));
+    let mut goals = vec![];
+    fill(
+        &mut tubes,
+        &counts.into_iter().collect::<Vec<_>>(),
+        0,
+        &mut goals,
+    );
+    let mut unique: Vec<Game> = vec![];
+    for mut goal in goals {
+        goal.sort();
+        if symmetry {
+            goal.canonicalize();
+        }
+        if !unique.contains(&goal) {
+            unique.push(goal);
+        }
+    }
+    unique
+}
+
+/// Fills free tubes with the colors from `colors[next..]`, pushing a game
+/// for every way that uses up all of their units. Free tubes are the empty
+/// ones, taken in order of capacity so that tubes of a size fill up first to
+/// last.
+fn fill(tubes: &mut Vec<Tube>, colors: &[(Color, usize)], next: usize, goals: &mut Vec<Game>) {
+    let Some(&(color, count)) = colors.get(next) else {
+        goals.push(Game {
+            size: tubes.len(),
+            state: tubes.clone(),
+        });
+        return;
+    };
+    fill_color(tubes, colors, next, color, count, 0, goals);
+}
+
+/// Fills tubes from `from` on with `left` more units of `color`, then moves
+/// on to the next color.
+fn fill_color(
+    tubes: &mut Vec<Tube>,
+    colors: &[(Color, usize)],
+    next: usize,
+    color: Color,
+    left: usize,
+    from: usize,
+    goals: &mut Vec<Game>,
+) {
+    if left == 0 {
+        fill(tubes, colors, next + 1, goals);
+        return;
+    }
+    let mut last_capacity = None;
+    for i in from..tubes.len() {
+        let capacity = tubes[i].capacity;
+        // One tube of each size is enough, the rest give the same game
+        let free = tubes[i].colors().iter().all(Color::is_empty);
+        if !free || capacity > left || last_capacity == Some(capacity) {
+            continue;
+        }
+        last_capacity = Some(capacity);
+        tubes[i].colors[..capacity].fill(color);
+        fill_color(tubes, colors, next, color, left - capacity, i + 1, goals);
+        tubes[i].colors[..capacity].fill(Color::Empty);
+    }
+}
+
+/// The games that one pour turns into `game`, each with that pour. A pour
+/// moves the top block of its tube, or as much of it as fits, so undoing it
+/// takes back some of the block on top of the tube poured into.
+fn unpours(game: &Game, symmetry: bool) -> Vec<((usize, usize), Game)> {
+    let mut games: Vec<((usize, usize), Game)> = vec![];
+    let mut seen = HashSet::new();
+    for (b, into) in game.state.iter().enumerate() {
+        let color = into.top();
+        let block = into.top_block();
+        let held = into.capacity - into.room();
+        for (a, from) in game.state.iter().enumerate() {
+            if a == b {
+                continue;
+            }
+            // Had the tube poured from more of the color under what was
+            // poured, the pour only stopped because `into` was full
+            let more = from.top() == color;
+            if more && into.room() > 0 {
+                continue;
+            }
+            for amount in 1..=block.min(from.room()) {
+                // What was under the poured units had to take the color
+                if amount == block && amount < held {
+                    continue;
+                }
+                let mut before = game.clone();
+                let room = from.room();
+                before.state[a].colors[room - amount..room].fill(color);
+                let top = into.room();
+                before.state[b].colors[top..top + amount].fill(Color::Empty);
+                let step = (from.id, into.id);
+                before.sort();
+                if symmetry {
+                    before.canonicalize();
+                }
+                if seen.insert(before.clone()) {
+                    games.push((step, before));
+                }
+            }
+        }
+    }
+    games
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Limits;
+
+    #[test]
+    fn unpours_undo_pours() {
+        let games = [
+            Game::new(vec![[0, 1, 1, 2], [0, 0, 1, 2], [0, 0, 0, 1], [2, 2, 1, 1]]),
+            Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]),
+            Game::mixed(vec![vec![0, 1, 2], vec![0, 0, 1, 1, 2], vec![1, 2, 2]]).unwrap(),
+        ];
+        for game in games {
+            let mut game = game;
+            game.sort();
+            for (step, before) in unpours(&game, false) {
+                let mut after = before.clone();
+                let (a, b) = (after.tube(step.0).unwrap(), after.tube(step.1).unwrap());
+                assert!(after.pour(a, b), "{before:?} can't pour {step:?}");
+                after.sort();
+                assert_eq!(after, game);
+            }
+            // And every game one pour away from another shows up undone
+            for (_, child) in game.moves(&Pruning::none(), &mut Pruned::default()) {
+                let befores = unpours(&child, false);
+                assert!(befores.iter().any(|(_, before)| *before == game));
+            }
+        }
+    }
+
+    #[test]
+    fn solved_game_needs_no_moves() {
+        // The 1s come first, where the sorted goal has the empty tube first
+        let game = Game::new(vec![[1; 4], [2; 4], [0; 4]]);
+        let solution = search(&game, &mut Budget::new(&Limits::default()));
+        assert_eq!(solution, Ok(Some(vec![])));
+    }
+
+    #[test]
+    fn goals_are_solved() {
+        let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]);
+        let solved = goals(&game, false);
+        assert_eq!(solved.len(), 1);
+        assert!(solved[0].is_solved());
+
+        // Four units fill either one tall tube or two short ones
+        let game = Game::mixed(vec![
+            vec![1, 2, 1, 2],
+            vec![2, 1, 2, 1],
+            vec![0; 2],
+            vec![0; 2],
+        ])
+        .unwrap();
+        let goals = goals(&game, false);
+        assert_eq!(goals.len(), 3);
+        for goal in goals {
+            assert!(goal.is_solved());
+            assert_eq!(goal.state.len(), game.state.len());
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use std::{collections::HashSet, fmt};
+
+use crate::{
+    search::{Budget, Limit},
+    store::Store,
+    CertificateError, Game, Gravity, Solution,
+};
+
+/// Proof that a game can't be solved. It sums up every game reachable from
+/// the start by the number of them and a digest, with colors renamed as by
+/// [`Game::canonicalize`], and lists the reachable games where no pour is
+/// possible. Get one from [`Solver::certify`](crate::Solver::certify) and
+/// check it with [`Certificate::verify`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Certificate {
+    /// The number of games reachable from the start, counting the start and
+    /// counting games that [`Game::canonicalize`] renames alike once.
+    pub states: usize,
+    /// The digests of the reachable games added up, which is the same
+    /// whatever order they are found in.
+    pub digest: u64,
+    /// The reachable games where no pour is possible.
+    pub dead_ends: Vec<Game>,
+}
+
+impl Certificate {
+    /// Checks the certificate for `game` by finding every reachable game
+    /// again, pouring every tube into every other one rather than sharing any
+    /// of the solver's shortcuts. The game is settled first, as the solver
+    /// does.
+    pub fn verify(&self, game: &Game) -> Result<(), CertificateError> {
+        let mut start = game.clone();
+        start
+            .normalize(Gravity::Settle)
+            .expect("Settling never fails");
+        start.canonicalize();
+        let mut seen = HashSet::from([start.clone()]);
+        let mut todo = vec![start];
+        let mut dead_ends = HashSet::new();
+        while let Some(game) = todo.pop() {
+            if game.is_solved() {
+                return Err(CertificateError::Solvable);
+            }
+            let mut stuck = true;
+            for a in 0..game.state.len() {
+                for b in 0..game.state.len() {
+                    let mut child = game.clone();
+                    if child.pour(a, b) {
+                        stuck = false;
+                        child.canonicalize();
+                        if seen.insert(child.clone()) {
+                            todo.push(child);
+                        }
+                    }
+                }
+            }
+            if stuck {
+                dead_ends.insert(game);
+            }
+        }
+
+        if seen.len() != self.states {
+            return Err(CertificateError::States {
+                claimed: self.states,
+                found: seen.len(),
+            });
+        }
+        let found = seen.iter().map(digest).fold(0, u64::wrapping_add);
+        if found != self.digest {
+            return Err(CertificateError::Digest {
+                claimed: self.digest,
+                found,
+            });
+        }
+        let claimed: HashSet<&Game> = self.dead_ends.iter().collect();
+        if claimed.len() != self.dead_ends.len() || claimed != dead_ends.iter().collect() {
+            return Err(CertificateError::DeadEnds {
+                claimed: self.dead_ends.len(),
+                found: dead_ends.len(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Certificate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} reachable games with digest {:016x}, {} of them dead ends",
+            self.states,
+            self.digest,
+            self.dead_ends.len()
+        )
+    }
+}
+
+/// What searching every reachable game turned up.
+pub(crate) enum Proof {
+    Unsolvable(Certificate),
+    /// A shortest solution, found before every game was reached.
+    Solved(Solution),
+}
+
+/// Searches breadth first through every game reachable from `game`, with
+/// the colors of each renamed. The budget has to rename them and keep every
+/// move, so that the games counted are exactly the ones
+/// [`Certificate::verify`] finds.
+pub(crate) fn search(game: &Game, budget: &mut Budget) -> Result<Proof, Limit> {
+    let mut start = game.clone();
+    start.canonicalize();
+    if start.is_solved() {
+        return Ok(Proof::Solved(vec![]));
+    }
+    let mut games = Store::new();
+    let mut layer = vec![games.insert(&start, None).expect("Store starts empty")];
+    let mut certificate = Certificate {
+        states: 1,
+        digest: digest(&start),
+        dead_ends: vec![],
+    };
+
+    for depth in 0.. {
+        if budget.too_deep(depth + 1) {
+            return Err(Limit::Depth);
+        }
+        let mut next = vec![];
+        for index in layer {
+            budget.expand(depth)?;
+            let game = games.game(index);
+            let children = budget.children(&game);
+            if children.is_empty() {
+                certificate.dead_ends.push(game);
+                continue;
+            }
+            for (step, child) in children {
+                if child.is_solved() {
+                    let mut solution = games.path(index);
+                    solution.push(step);
+                    budget.found(&solution);
+                    return Ok(Proof::Solved(solution));
+                }
+                if let Some(index) = games.insert(&child, Some((index, step))) {
+                    certificate.states += 1;
+                    certificate.digest = certificate.digest.wrapping_add(digest(&child));
+                    next.push(index);
+                }
+            }
+        }
+        budget.progress(depth + 1, next.len(), games.len());
+        if next.is_empty() {
+            break;
+        }
+        layer = next;
+    }
+    certificate.dead_ends.sort_by(|a, b| a.state.cmp(&b.state));
+    Ok(Proof::Unsolvable(certificate))
+}
+
+/// A hash of a sorted game that, unlike [`Hash`](std::hash::Hash), comes
+/// out the same on every run and every machine.
+fn digest(game: &Game) -> u64 {
+    let mut hash = game.state.len() as u64;
+    for tube in &game.state {
+        hash = mix(hash ^ tube.key());
+    }
+    hash
+}
+
+/// The finalizer of SplitMix64, spreading every bit of `x` over the result.
+pub(crate) fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ExtraBlocks, SolveOutcome, Solver, Strategy};
+
+    fn unsolvable() -> Game {
+        Game::new(vec![[3, 2, 1, 1], [2, 3, 2, 1], [3, 2, 3, 1], [0; 4]])
+    }
+
+    #[test]
+    fn certifies_and_verifies() {
+        let game = unsolvable();
+        let certificate = Solver::new(game.clone()).unwrap().certify().unwrap();
+        assert!(certificate.states > 1);
+        assert!(!certificate.dead_ends.is_empty());
+        assert_eq!(certificate.verify(&game), Ok(()));
+
+        // The certificate doesn't depend on the names of the colors
+        let renamed = Game::new(vec![[1, 3, 2, 2], [3, 1, 3, 2], [1, 3, 1, 2], [0; 4]]);
+        assert_eq!(certificate.verify(&renamed), Ok(()));
+    }
+
+    #[test]
+    fn rejects_wrong_certificates() {
+        let game = unsolvable();
+        let certificate = Solver::new(game.clone()).unwrap().certify().unwrap();
+
+        let mut wrong = certificate.clone();
+        wrong.states += 1;
+        assert!(matches!(
+            wrong.verify(&game),
+            Err(CertificateError::States { .. })
+        ));
+        let mut wrong = certificate.clone();
+        wrong.digest ^= 1;
+        assert!(matches!(
+            wrong.verify(&game),
+            Err(CertificateError::Digest { .. })
+        ));
+        let mut wrong = certificate.clone();
+        wrong.dead_ends.pop();
+        assert!(matches!(
+            wrong.verify(&game),
+            Err(CertificateError::DeadEnds { .. })
+        ));
+        let mut wrong = certificate.clone();
+        wrong.dead_ends.push(wrong.dead_ends[0].clone());
+        assert!(matches!(
+            wrong.verify(&game),
+            Err(CertificateError::DeadEnds { .. })
+        ));
+
+        let solvable = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]);
+        assert_eq!(
+            certificate.verify(&solvable),
+            Err(CertificateError::Solvable)
+        );
+    }
+
+    #[test]
+    fn solvable_games_get_a_solution() {
+        let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]);
+        let outcome = Solver::new(game.clone())
+            .unwrap()
+            .with_strategy(Strategy::Dfs(Box::new(ExtraBlocks)))
+            .certify()
+            .unwrap_err();
+        let SolveOutcome::Solved { depth, optimal, .. } = outcome else {
+            panic!("Expected a solution, got {outcome}");
+        };
+        assert!(optimal);
+        assert_eq!(
+            Some(depth),
+            Solver::new(game)
+                .unwrap()
+                .solve()
+                .into_solutions()
+                .map(|s| s[0].len())
+        );
+    }
+}
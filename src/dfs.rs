@@ -0,0 +1,94 @@
+use crate::{
+    search::{Budget, Limit},
+    store::Store,
+    Game, Heuristic, Solution,
+};
+
+/// How many games to expand between progress reports.
+const PROGRESS_INTERVAL: usize = 1 << 12;
+
+/// Follows the most promising move first and only backs up at dead ends,
+/// returning the first solution it reaches. Nothing makes that solution a
+/// shortest one.
+pub(crate) fn search(
+    game: &Game,
+    heuristic: &dyn Heuristic,
+    budget: &mut Budget,
+) -> Result<Option<Solution>, Limit> {
+    let mut states = Store::new();
+    let mut stack: Vec<usize> = states.insert(game, None).into_iter().collect();
+    while let Some(index) = stack.pop() {
+        let depth = states.depth(index);
+        let game = states.game(index);
+        if game.is_solved() {
+            let solution = states.path(index);
+            budget.found(&solution);
+            return Ok(Some(solution));
+        }
+        budget.expand(depth)?;
+        if budget.expanded().is_multiple_of(PROGRESS_INTERVAL) {
+            budget.progress(depth, stack.len(), states.len());
+        }
+        if budget.too_deep(depth + 1) {
+            continue;
+        }
+        let mut children = budget.children(&game);
+        children.retain(|(_, child)| states.find(child).is_none());
+        // The stack is popped from the back, so the best move goes last
+        children.sort_by_cached_key(|(_, child)| std::cmp::Reverse(heuristic.estimate(child)));
+        for (step, child) in children {
+            stack.extend(states.insert(&child, Some((index, step))));
+        }
+        budget.sizes(stack.len(), states.len());
+    }
+    budget.exhausted()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ExtraBlocks, Limits};
+
+    fn search(game: &Game, heuristic: &dyn Heuristic) -> Option<Solution> {
+        super::search(game, heuristic, &mut Budget::new(&Limits::default())).unwrap()
+    }
+
+    #[test]
+    fn finds_a_solution() {
+        let mut game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]);
+        let solution = search(&game, &ExtraBlocks).unwrap();
+        assert!(solution.len() >= 7);
+        for (a, b) in solution {
+            let (i, o) = (game.tube(a).unwrap(), game.tube(b).unwrap());
+            assert!(game.pour(i, o));
+        }
+        assert!(game.is_solved());
+    }
+
+    #[test]
+    fn solved_game_needs_no_moves() {
+        let game = Game::new(vec![[1; 4], [0; 4]]);
+        assert_eq!(search(&game, &ExtraBlocks), Some(vec![]));
+    }
+
+    #[test]
+    fn unsolvable_game() {
+        let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1]]);
+        assert_eq!(search(&game, &ExtraBlocks), None);
+    }
+
+    #[test]
+    fn state_limit() {
+        let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]);
+        let limits = Limits {
+            max_states: Some(3),
+            ..Limits::default()
+        };
+        let mut budget = Budget::new(&limits);
+        assert_eq!(
+            super::search(&game, &ExtraBlocks, &mut budget),
+            Err(Limit::States)
+        );
+        assert_eq!(budget.stats().expanded, 3);
+    }
+}
@@ -0,0 +1,412 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    env, fs,
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{Budget, Game, Limit, PackedGame, Pruned, Solution};
+
+/// Where a breadth first search keeps the games it has seen when they don't
+/// fit in memory, see [`Solver::with_disk`](crate::Solver::with_disk).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Disk {
+    /// The directory to make the search's own temporary directory in. It is
+    /// removed again when the search ends.
+    pub dir: PathBuf,
+    /// The most new games to hold in memory before sorting them out to a
+    /// file of their own.
+    pub games_in_memory: usize,
+}
+
+impl Default for Disk {
+    fn default() -> Self {
+        Disk {
+            dir: env::temp_dir(),
+            games_in_memory: 1 << 20,
+        }
+    }
+}
+
+/// A breadth first search that keeps its layers and the games it has seen in
+/// files, sorted so that duplicates can be dropped by merging them.
+///
+/// The children of each layer are sorted a memory's worth at a time into
+/// runs, and the runs are merged with the sorted file of every game seen so
+/// far. Whatever is new makes up the next layer and is merged into the seen
+/// games for the one after. Games only keep their colors and tube ids, so
+/// solutions are traced back by finding a parent for each game in the layer
+/// before it, once the last layer is known.
+pub(crate) fn search(
+    game: &Game,
+    disk: &Disk,
+    budget: &mut Budget,
+) -> Result<Option<Vec<Solution>>, Limit> {
+    if game.is_solved() {
+        let solution = vec![];
+        budget.found(&solution);
+        return Ok(Some(vec![solution]));
+    }
+    let dir = TempDir::new(&disk.dir)?;
+    let start = game.pack();
+    write_sorted(&dir.layer(0), [&start])?;
+    write_sorted(&dir.seen(0), [&start])?;
+    let mut visited = 1;
+
+    for depth in 0.. {
+        if budget.too_deep(depth + 1) {
+            return Err(Limit::Depth);
+        }
+
+        let mut runs = vec![];
+        let mut children = vec![];
+        let mut parents = vec![];
+        for parent in Games::open(&dir.layer(depth))? {
+            let parent = parent?;
+            budget.expand(depth)?;
+            let mut solved = false;
+            for (_, child) in budget.children(&parent.unpack()) {
+                if child.is_solved() {
+                    solved = true;
+                } else {
+                    children.push(child.pack());
+                }
+            }
+            if solved {
+                parents.push(parent);
+            }
+            if children.len() >= disk.games_in_memory.max(1) {
+                runs.push(dir.run(runs.len()));
+                write_run(runs.last().expect("Run was pushed"), &mut children)?;
+            }
+        }
+        if !children.is_empty() {
+            runs.push(dir.run(runs.len()));
+            write_run(runs.last().expect("Run was pushed"), &mut children)?;
+        }
+
+        let frontier = merge(&dir, depth, &runs)?;
+        visited += frontier;
+        for run in runs {
+            fs::remove_file(run)?;
+        }
+        fs::remove_file(dir.seen(depth))?;
+        budget.progress(depth + 1, frontier, visited);
+        if !parents.is_empty() {
+            return trace(&dir, depth, &parents, game, budget).map(Some);
+        }
+        if frontier == 0 {
+            return Ok(None);
+        }
+    }
+    unreachable!("Depths never run out")
+}
+
+/// Merges the sorted runs of children with the games seen before `depth + 1`,
+/// writing the new games out as the next layer and all of them as the games
+/// seen. Returns the number of new games.
+fn merge(dir: &TempDir, depth: usize, runs: &[PathBuf]) -> io::Result<usize> {
+    let mut seen = Sorted::open(&dir.seen(depth))?;
+    let mut layer = BufWriter::new(fs::File::create(dir.layer(depth + 1))?);
+    let mut all = BufWriter::new(fs::File::create(dir.seen(depth + 1))?);
+    let mut runs = runs
+        .iter()
+        .map(|run| Games::open(run))
+        .collect::<io::Result<Vec<_>>>()?;
+    let mut heap = BinaryHeap::new();
+    for (index, run) in runs.iter_mut().enumerate() {
+        if let Some(game) = run.next().transpose()? {
+            heap.push(Reverse((game, index)));
+        }
+    }
+
+    let mut new = 0;
+    let mut last: Option<PackedGame> = None;
+    while let Some(Reverse((game, index))) = heap.pop() {
+        if let Some(next) = runs[index].next().transpose()? {
+            heap.push(Reverse((next, index)));
+        }
+        if last.as_ref() == Some(&game) {
+            continue;
+        }
+        while seen.peek().is_some_and(|old| *old < game) {
+            seen.take()?.write_to(&mut all)?;
+        }
+        if seen.peek() != Some(&game) {
+            game.write_to(&mut layer)?;
+            game.write_to(&mut all)?;
+            new += 1;
+        }
+        last = Some(game);
+    }
+    while seen.peek().is_some() {
+        seen.take()?.write_to(&mut all)?;
+    }
+    layer.flush()?;
+    all.flush()?;
+    Ok(new)
+}
+
+/// Finds the moves to every solution, given the games `depth` moves deep that
+/// have a solved child.
+fn trace(
+    dir: &TempDir,
+    depth: usize,
+    parents: &[PackedGame],
+    start: &Game,
+    budget: &mut Budget,
+) -> Result<Vec<Solution>, Limit> {
+    // Each chain runs from a parent of a solved game back to the start
+    let mut chains: Vec<Vec<PackedGame>> = parents.iter().map(|game| vec![game.clone()]).collect();
+    let mut pruned = Pruned::default();
+    for layer in (0..depth).rev() {
+        let mut wanted: HashMap<PackedGame, Vec<usize>> = HashMap::new();
+        for (index, chain) in chains.iter().enumerate() {
+            let last = chain.last().expect("Chains start with a game");
+            wanted.entry(last.clone()).or_default().push(index);
+        }
+        for game in Games::open(&dir.layer(layer))? {
+            let game = game?;
+            for (_, child) in budget.expansion.children(&game.unpack(), &mut pruned) {
+                for index in wanted.remove(&child.pack()).unwrap_or_default() {
+                    chains[index].push(game.clone());
+                }
+            }
+            if wanted.is_empty() {
+                break;
+            }
+        }
+    }
+
+    // Replay each chain from the start, as equal games can have their tube
+    // ids in a different order
+    let mut solutions = vec![];
+    for chain in chains {
+        let mut game = start.clone();
+        let mut moves = Vec::with_capacity(depth + 1);
+        for next in chain.iter().rev().skip(1) {
+            let (step, child) = budget
+                .expansion
+                .children(&game, &mut pruned)
+                .into_iter()
+                .find(|(_, child)| child.pack() == *next)
+                .expect("Every game in a chain is a child of the one before");
+            moves.push(step);
+            game = child;
+        }
+        for (step, child) in budget.expansion.children(&game, &mut pruned) {
+            if child.is_solved() {
+                let mut solution = moves.clone();
+                solution.push(step);
+                budget.found(&solution);
+                solutions.push(solution);
+            }
+        }
+    }
+    Ok(solutions)
+}
+
+/// Sorts `games`, drops duplicates and writes them to `path`, leaving
+/// `games` empty.
+fn write_run(path: &Path, games: &mut Vec<PackedGame>) -> io::Result<()> {
+    games.sort_unstable();
+    games.dedup();
+    write_sorted(path, games.iter())?;
+    games.clear();
+    Ok(())
+}
+
+fn write_sorted<'a>(
+    path: &Path,
+    games: impl IntoIterator<Item = &'a PackedGame>,
+) -> io::Result<()> {
+    let mut out = BufWriter::new(fs::File::create(path)?);
+    for game in games {
+        game.write_to(&mut out)?;
+    }
+    out.flush()
+}
+
+/// The games in a file, in the order they were written.
+struct Games(BufReader<fs::File>);
+
+impl Games {
+    fn open(path: &Path) -> io::Result<Self> {
+        Ok(Games(BufReader::new(fs::File::open(path)?)))
+    }
+}
+
+impl Iterator for Games {
+    type Item = io::Result<PackedGame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        PackedGame::read_from(&mut self.0).transpose()
+    }
+}
+
+/// The games in a sorted file, looking one game ahead.
+struct Sorted {
+    games: Games,
+    next: Option<PackedGame>,
+}
+
+impl Sorted {
+    fn open(path: &Path) -> io::Result<Self> {
+        let mut games = Games::open(path)?;
+        let next = games.next().transpose()?;
+        Ok(Sorted { games, next })
+    }
+
+    fn peek(&self) -> Option<&PackedGame> {
+        self.next.as_ref()
+    }
+
+    /// Takes the game [`peek`](Sorted::peek) returned, which must be there.
+    fn take(&mut self) -> io::Result<PackedGame> {
+        let next = self.games.next().transpose()?;
+        Ok(std::mem::replace(&mut self.next, next).expect("A game was peeked"))
+    }
+}
+
+/// A directory of its own for one search, removed with everything in it when
+/// dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(parent: &Path) -> io::Result<Self> {
+        static SEARCHES: AtomicUsize = AtomicUsize::new(0);
+        let search = SEARCHES.fetch_add(1, Ordering::Relaxed);
+        let path = parent.join(format!("tube-{}-{search}", process::id()));
+        fs::create_dir_all(parent)?;
+        fs::create_dir(&path)?;
+        Ok(TempDir(path))
+    }
+
+    /// The new games `depth` moves deep.
+    fn layer(&self, depth: usize) -> PathBuf {
+        self.0.join(format!("layer-{depth}"))
+    }
+
+    /// Every game up to `depth` moves deep.
+    fn seen(&self, depth: usize) -> PathBuf {
+        self.0.join(format!("seen-{depth}"))
+    }
+
+    fn run(&self, index: usize) -> PathBuf {
+        self.0.join(format!("run-{index}"))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        // Nothing to be done about a directory that can't be removed
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SolveOutcome, Solver};
+
+    fn small_disk() -> Disk {
+        Disk {
+            games_in_memory: 5,
+            ..Disk::default()
+        }
+    }
+
+    #[test]
+    fn same_as_memory() {
+        let games = [
+            Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]),
+            Game::new(vec![
+                [1, 2, 3, 4],
+                [2, 3, 4, 1],
+                [3, 4, 1, 2],
+                [4, 1, 2, 3],
+                [0; 4],
+                [0; 4],
+            ]),
+            Game::mixed(vec![vec![1, 2, 1, 2], vec![2, 1, 2, 1], vec![0; 6]]).unwrap(),
+        ];
+        for game in games {
+            let plain = Solver::new(game.clone()).unwrap().solve();
+            let outcome = Solver::new(game.clone())
+                .unwrap()
+                .with_disk(small_disk())
+                .solve();
+            assert_eq!(outcome.stats().visited, plain.stats().visited);
+            assert_eq!(outcome.stats().expanded, plain.stats().expanded);
+            let plain = plain.into_solutions().unwrap();
+            let solutions = outcome.into_solutions().unwrap();
+            assert_eq!(solutions.len(), plain.len());
+            for solution in solutions {
+                assert_eq!(solution.len(), plain[0].len());
+                let mut game = game.clone();
+                for (a, b) in solution {
+                    let (i, o) = (game.tube(a).unwrap(), game.tube(b).unwrap());
+                    assert!(game.pour(i, o));
+                }
+                assert!(game.is_solved());
+            }
+        }
+    }
+
+    #[test]
+    fn solved_start() {
+        let game = Game::new(vec![[1; 4], [2; 4], [0; 4]]);
+        let outcome = Solver::new(game).unwrap().with_disk(small_disk()).solve();
+        assert!(matches!(outcome, SolveOutcome::Solved { depth: 0, .. }));
+        assert_eq!(outcome.into_solutions(), Some(vec![vec![]]));
+    }
+
+    #[test]
+    fn unsolvable() {
+        let game = Game::new(vec![[3, 2, 1, 1], [2, 3, 2, 1], [3, 2, 3, 1], [0; 4]]);
+        let plain = Solver::new(game.clone()).unwrap().solve();
+        let outcome = Solver::new(game).unwrap().with_disk(small_disk()).solve();
+        assert!(matches!(outcome, SolveOutcome::Unsolvable { .. }));
+        assert_eq!(outcome.stats().visited, plain.stats().visited);
+    }
+
+    #[test]
+    fn cleans_up() {
+        let dir = env::temp_dir().join(format!("tube-test-{}", process::id()));
+        let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]);
+        let disk = Disk {
+            dir: dir.clone(),
+            ..small_disk()
+        };
+        assert!(Solver::new(game)
+            .unwrap()
+            .with_disk(disk)
+            .get_solutions()
+            .is_some());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn reports_io_errors() {
+        let file = env::temp_dir().join(format!("tube-test-file-{}", process::id()));
+        fs::write(&file, "").unwrap();
+        let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]);
+        let disk = Disk {
+            dir: file.join("under a file"),
+            ..small_disk()
+        };
+        let outcome = Solver::new(game).unwrap().with_disk(disk).solve();
+        fs::remove_file(&file).unwrap();
+        assert!(matches!(
+            outcome,
+            SolveOutcome::Aborted {
+                limit: Limit::Io(_),
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,227 @@
+use std::{error::Error, fmt};
+
+use crate::{Color, MAX_CAPACITY};
+
+/// Why a game can't be solved as entered.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValidationError {
+    /// The game has no tubes at all.
+    NoTubes,
+    /// A color has fewer units than the tube it should fill.
+    TooFew {
+        color: Color,
+        count: usize,
+        expected: usize,
+        tubes: Vec<usize>,
+    },
+    /// A color has more units than the tube it should fill.
+    TooMany {
+        color: Color,
+        count: usize,
+        expected: usize,
+        tubes: Vec<usize>,
+    },
+    /// More colors need a tube of `capacity` than there are such tubes, and
+    /// the colors can't all fill whole tubes in any other way.
+    NotEnoughTubes {
+        capacity: usize,
+        colors: Vec<Color>,
+        tubes: Vec<usize>,
+    },
+    /// Each color could fill whole tubes on its own, but not all of them at
+    /// once.
+    NoArrangement { colors: Vec<Color> },
+    /// The empty slots don't add up to what some set of tubes holds, so
+    /// the tubes left over once every color fills its own can't be empty.
+    EmptySlots {
+        found: usize,
+        expected: usize,
+        tubes: Vec<usize>,
+    },
+    /// A color sits above an empty slot in the same tube.
+    FloatingLiquid { color: Color, tube: usize },
+    /// A tube holds nothing or more than [`MAX_CAPACITY`](crate::MAX_CAPACITY).
+    BadCapacity { tube: usize, capacity: usize },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::NoTubes => write!(f, "The game has no tubes"),
+            ValidationError::TooFew {
+                color,
+                count,
+                expected,
+                tubes,
+            }
+            | ValidationError::TooMany {
+                color,
+                count,
+                expected,
+                tubes,
+            } => write!(
+                f,
+                "Color {color:?} appears {count} times in tubes {tubes:?}, expected {expected}"
+            ),
+            ValidationError::NotEnoughTubes {
+                capacity,
+                colors,
+                tubes,
+            } => write!(
+                f,
+                "Colors {colors:?} each need a tube holding {capacity} but only tubes {tubes:?} do"
+            ),
+            ValidationError::NoArrangement { colors } => {
+                write!(f, "Colors {colors:?} can't all fill whole tubes at once")
+            }
+            ValidationError::EmptySlots {
+                found,
+                expected,
+                tubes,
+            } => write!(
+                f,
+                "Found {found} empty slots in tubes {tubes:?}, expected {expected}"
+            ),
+            ValidationError::FloatingLiquid { color, tube } => {
+                write!(
+                    f,
+                    "Color {color:?} floats above an empty slot in tube {tube}"
+                )
+            }
+            ValidationError::BadCapacity { tube, capacity } => write!(
+                f,
+                "Tube {tube} holds {capacity}, but tubes hold 1 to {MAX_CAPACITY}"
+            ),
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+/// Why a [`Certificate`](crate::Certificate) doesn't prove its game
+/// unsolvable.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CertificateError {
+    /// A solved game can be reached after all.
+    Solvable,
+    /// A different number of games can be reached.
+    States { claimed: usize, found: usize },
+    /// The same number of games can be reached, but not the same ones.
+    Digest { claimed: u64, found: u64 },
+    /// The games with no moves left are not the ones listed.
+    DeadEnds { claimed: usize, found: usize },
+}
+
+impl fmt::Display for CertificateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CertificateError::Solvable => write!(f, "A solved game can be reached"),
+            CertificateError::States { claimed, found } => {
+                write!(f, "Claims {claimed} reachable games, found {found}")
+            }
+            CertificateError::Digest { claimed, found } => write!(
+                f,
+                "Claims digest {claimed:016x} of the reachable games, found {found:016x}"
+            ),
+            CertificateError::DeadEnds { claimed, found } => write!(
+                f,
+                "Claims {claimed} dead ends, found {found} that don't all match"
+            ),
+        }
+    }
+}
+
+impl Error for CertificateError {}
+
+/// Where and why a puzzle in the text format couldn't be read, see
+/// [`Puzzle::parse`](crate::Puzzle::parse).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// The line the problem is on, counting from 1.
+    pub line: usize,
+    /// The character the problem starts at, counting from 1.
+    pub column: usize,
+    pub kind: ParseErrorKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// A slot that is neither a color nor empty.
+    BadColor(String),
+    /// A tube listing more slots than it can hold.
+    TooManySlots { slots: usize, capacity: usize },
+    /// A metadata key other than `level` or `capacity`.
+    UnknownKey(String),
+    /// A metadata key given twice.
+    DuplicateKey(String),
+    /// A metadata value that isn't a number, or a capacity no tube can have.
+    BadValue(String),
+    /// Metadata after the first tube.
+    LateMetadata,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Line {}, column {}: ", self.line, self.column)?;
+        match &self.kind {
+            ParseErrorKind::BadColor(word) => write!(f, "{word:?} is not a color"),
+            ParseErrorKind::TooManySlots { slots, capacity } => {
+                write!(f, "Tube lists {slots} slots but holds {capacity}")
+            }
+            ParseErrorKind::UnknownKey(key) => write!(f, "Unknown key {key:?}"),
+            ParseErrorKind::DuplicateKey(key) => write!(f, "{key:?} is given twice"),
+            ParseErrorKind::BadValue(value) => write!(f, "{value:?} is not a valid value"),
+            ParseErrorKind::LateMetadata => write!(f, "Metadata has to come before the tubes"),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// Which move of a solution can't be made and why, see
+/// [`verify_solution`](crate::verify_solution).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MoveError {
+    /// Which move it is, counting from 1.
+    pub number: usize,
+    /// The id of the tube poured from.
+    pub from: usize,
+    /// The id of the tube poured into.
+    pub to: usize,
+    pub kind: MoveErrorKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveErrorKind {
+    /// No tube has this id.
+    UnknownId(usize),
+    /// The tube would pour into itself.
+    SameTube,
+    /// The tube poured from holds nothing.
+    SourceEmpty,
+    /// The tube poured into has no room.
+    TargetFull,
+    /// The color poured doesn't match the one on top of the other tube.
+    ColorMismatch { poured: Color, onto: Color },
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Move {}, tube {} into tube {}: ",
+            self.number, self.from, self.to
+        )?;
+        match self.kind {
+            MoveErrorKind::UnknownId(id) => write!(f, "There is no tube {id}"),
+            MoveErrorKind::SameTube => write!(f, "A tube can't pour into itself"),
+            MoveErrorKind::SourceEmpty => write!(f, "Tube {} is empty", self.from),
+            MoveErrorKind::TargetFull => write!(f, "Tube {} is full", self.to),
+            MoveErrorKind::ColorMismatch { poured, onto } => {
+                write!(f, "Can't pour {poured:?} onto {onto:?}")
+            }
+        }
+    }
+}
+
+impl Error for MoveError {}
@@ -0,0 +1,114 @@
+use std::{collections::BTreeMap, fmt};
+
+use crate::{Color, Game};
+
+/// Estimates how many moves are left before a game is solved. A* only
+/// keeps its shortest-solution guarantee when the estimate never overshoots
+/// the real number of moves. Heuristics are `Send` so that a [`Solver`]
+/// holding one can run on another thread.
+///
+/// [`Solver`]: crate::Solver
+pub trait Heuristic: fmt::Debug + Send {
+    fn estimate(&self, game: &Game) -> usize;
+}
+
+/// Counts every place where one color sits directly on another. A pour only
+/// takes liquid off the top of a tube and never leaves it on a different
+/// color, so each move removes at most one boundary.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Boundaries;
+
+impl Heuristic for Boundaries {
+    fn estimate(&self, game: &Game) -> usize {
+        game.state
+            .iter()
+            .map(|tube| {
+                tube.colors()
+                    .windows(2)
+                    .filter(|pair| pair[0].is_value() && pair[1].is_value() && pair[0] != pair[1])
+                    .count()
+            })
+            .sum()
+    }
+}
+
+/// Counts the blocks of liquid beyond those left once solved. A pour moves a
+/// single block and at best merges it into another, so each move removes at
+/// most one block. A color can end up split across several tubes with a
+/// block in each, so it is allowed as many tubes as can hold its units
+/// between them. This never estimates less than [`Boundaries`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExtraBlocks;
+
+impl Heuristic for ExtraBlocks {
+    fn estimate(&self, game: &Game) -> usize {
+        let mut units: BTreeMap<Color, usize> = BTreeMap::new();
+        let mut blocks: usize = 0;
+        for tube in &game.state {
+            let mut above = Color::Empty;
+            for &color in tube.colors() {
+                if color.is_value() {
+                    *units.entry(color).or_default() += 1;
+                    if color != above {
+                        blocks += 1;
+                    }
+                }
+                above = color;
+            }
+        }
+        // The most tubes holding each amount between them
+        let largest = units.values().copied().max().unwrap_or(0);
+        let mut most: Vec<Option<usize>> = vec![None; largest + 1];
+        most[0] = Some(0);
+        for tube in &game.state {
+            for sum in (tube.capacity()..=largest).rev() {
+                if let Some(rest) = most[sum - tube.capacity()] {
+                    most[sum] = most[sum].max(Some(rest + 1));
+                }
+            }
+        }
+        // A color no tubes hold exactly can't be solved, so one block is as
+        // good a guess as any
+        let kept: usize = units.values().map(|&count| most[count].unwrap_or(1)).sum();
+        blocks
+            .saturating_sub(kept.min(game.state.len()))
+            .max(Boundaries.estimate(game))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solved_games_estimate_zero() {
+        let game = Game::new(vec![[1; 4], [2; 4], [0; 4]]);
+        assert_eq!(Boundaries.estimate(&game), 0);
+        assert_eq!(ExtraBlocks.estimate(&game), 0);
+    }
+
+    #[test]
+    fn boundaries() {
+        let game = Game::new(vec![[1, 2, 2, 1], [0, 2, 2, 1], [0; 4]]);
+        assert_eq!(Boundaries.estimate(&game), 3);
+    }
+
+    #[test]
+    fn extra_blocks() {
+        let game = Game::new(vec![[1, 2, 2, 1], [0, 2, 2, 1], [0; 4]]);
+        assert_eq!(ExtraBlocks.estimate(&game), 3);
+        let game = Game::new(vec![[0, 0, 1, 1], [0, 0, 1, 1], [2; 4]]);
+        assert_eq!(Boundaries.estimate(&game), 0);
+        assert_eq!(ExtraBlocks.estimate(&game), 1);
+    }
+
+    #[test]
+    fn extra_blocks_allow_split_colors() {
+        // Color 1 is solved in both short tubes
+        let game = Game::mixed(vec![vec![1, 1], vec![1, 1], vec![2; 4], vec![0; 4]]).unwrap();
+        assert_eq!(ExtraBlocks.estimate(&game), 0);
+        // Two colors in one tall tube still have a boundary between them
+        let game = Game::mixed(vec![vec![1, 1, 2, 2], vec![0; 2], vec![0; 2]]).unwrap();
+        assert_eq!(ExtraBlocks.estimate(&game), 1);
+    }
+}
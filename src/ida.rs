@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+
+use crate::{
+    search::{Budget, Limit},
+    Game, Heuristic, PackedGame, Solution,
+};
+
+/// Entries kept in the transposition table before it is cleared.
+const TABLE_SIZE: usize = 1 << 20;
+
+enum Outcome {
+    Solved(Solution),
+    /// The cheapest estimated total that went over the bound.
+    Exceeded(usize),
+    /// Nothing below this game goes over the bound or solves it.
+    Exhausted,
+}
+
+/// Searches depth first, only following games whose moves made plus the
+/// estimate of moves left stays within a bound, and raises the bound to the
+/// cheapest game that went over it each round. Memory only grows with the
+/// depth of the solution and the small transposition table.
+pub(crate) fn search(
+    game: &Game,
+    heuristic: &dyn Heuristic,
+    budget: &mut Budget,
+) -> Result<Option<Solution>, Limit> {
+    let mut search = IdaStar {
+        heuristic,
+        budget,
+        path: vec![],
+        longest: 0,
+        moves: vec![],
+        table: HashMap::new(),
+    };
+    let mut bound = heuristic.estimate(game);
+    loop {
+        if search.budget.too_deep(bound) {
+            return Err(Limit::Depth);
+        }
+        search.longest = 0;
+        let (outcome, _) = search.visit(game, 0, bound)?;
+        search
+            .budget
+            .progress(bound, search.longest, search.table.len());
+        match outcome {
+            Outcome::Solved(moves) => {
+                search.budget.found(&moves);
+                return Ok(Some(moves));
+            }
+            Outcome::Exceeded(next) => bound = next,
+            Outcome::Exhausted => return Ok(None),
+        }
+    }
+}
+
+struct IdaStar<'a> {
+    heuristic: &'a dyn Heuristic,
+    budget: &'a mut Budget,
+    /// The games on the current path, to avoid walking in circles.
+    path: Vec<PackedGame>,
+    /// The most games on the path at once this iteration.
+    longest: usize,
+    /// The moves that led to the game being visited.
+    moves: Vec<(usize, usize)>,
+    /// The largest number of moves each game was searched with without
+    /// finding a solution, and how far past that the cheapest game went.
+    /// Searches that left out a game for being on the path keep no more than
+    /// what holds however the game is reached.
+    table: HashMap<PackedGame, (usize, Option<usize>)>,
+}
+
+impl IdaStar<'_> {
+    /// Searches below `game`, also telling whether the outcome depends on the
+    /// path taken to it, because a game on that path was left out.
+    fn visit(&mut self, game: &Game, depth: usize, bound: usize) -> Result<(Outcome, bool), Limit> {
+        let total = depth + self.heuristic.estimate(game);
+        if total > bound {
+            return Ok((Outcome::Exceeded(total), false));
+        }
+        if game.is_solved() {
+            return Ok((Outcome::Solved(self.moves.clone()), false));
+        }
+        let key = game.pack();
+        let budget = bound - depth;
+        if let Some(&(searched, over)) = self.table.get(&key) {
+            if searched >= budget {
+                let outcome = match over {
+                    Some(over) => Outcome::Exceeded(depth + over),
+                    None => Outcome::Exhausted,
+                };
+                return Ok((outcome, false));
+            }
+        }
+
+        self.budget.expand(depth)?;
+        self.budget.sizes(self.path.len() + 1, self.table.len());
+        self.path.push(key);
+        self.longest = self.longest.max(self.path.len());
+        let mut children = self.budget.children(game);
+        let before = children.len();
+        children.retain(|(_, child)| !self.path.contains(&child.pack()));
+        let mut on_path = children.len() < before;
+        children.sort_by_cached_key(|(_, child)| self.heuristic.estimate(child));
+        let mut next: Option<usize> = None;
+        for (step, child) in children {
+            self.moves.push(step);
+            let (outcome, below) = self.visit(&child, depth + 1, bound)?;
+            self.moves.pop();
+            on_path |= below;
+            match outcome {
+                Outcome::Solved(moves) => return Ok((Outcome::Solved(moves), false)),
+                Outcome::Exceeded(total) => next = Some(next.map_or(total, |next| next.min(total))),
+                Outcome::Exhausted => (),
+            }
+        }
+        let key = self.path.pop().expect("The game was pushed");
+
+        // Reached another way, the games left out for being on the path may
+        // lead somewhere cheaper, so then only keep that nothing within the
+        // budget solves the game, and nothing at all if it looked exhausted.
+        // Were that wrong, a game on the path would have a solution within
+        // the bound, and this round would find one.
+        let over = next.map(|next| if on_path { budget + 1 } else { next - depth });
+        if !on_path || over.is_some() {
+            if self.table.len() >= TABLE_SIZE {
+                self.table.clear();
+            }
+            self.table.insert(key, (budget, over));
+        }
+        let outcome = match next {
+            Some(next) => Outcome::Exceeded(next),
+            None => Outcome::Exhausted,
+        };
+        Ok((outcome, on_path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Boundaries, ExtraBlocks, Limits, SolveOutcome, Solver};
+
+    fn search(game: &Game, heuristic: &dyn Heuristic) -> Option<Solution> {
+        super::search(game, heuristic, &mut Budget::new(&Limits::default())).unwrap()
+    }
+
+    #[test]
+    fn finds_shortest_solution() {
+        let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]);
+        assert_eq!(search(&game, &Boundaries).unwrap().len(), 7);
+        assert_eq!(search(&game, &ExtraBlocks).unwrap().len(), 7);
+    }
+
+    #[test]
+    fn finds_shortest_solution_with_mixed_capacities() {
+        // Games are often left out for being on the path here, so the table
+        // must only keep what holds however they are reached
+        for game in [
+            Game::mixed(vec![
+                vec![2, 1, 1, 2],
+                vec![1, 2, 2, 1],
+                vec![0; 2],
+                vec![0; 2],
+            ]),
+            Game::mixed(vec![vec![1, 2, 2, 2], vec![2, 1], vec![1, 1], vec![0; 4]]),
+            Game::mixed(vec![vec![1, 2, 1, 2], vec![2, 1, 2, 1], vec![0; 6]]),
+        ] {
+            let game = game.unwrap();
+            let shortest = match Solver::new(game.clone()).unwrap().solve() {
+                SolveOutcome::Solved { depth, .. } => depth,
+                outcome => panic!("Expected a solution, got {outcome:?}"),
+            };
+            assert_eq!(search(&game, &Boundaries).unwrap().len(), shortest);
+            assert_eq!(search(&game, &ExtraBlocks).unwrap().len(), shortest);
+        }
+    }
+
+    #[test]
+    fn solved_game_needs_no_moves() {
+        let game = Game::new(vec![[1; 4], [0; 4]]);
+        assert_eq!(search(&game, &ExtraBlocks), Some(vec![]));
+    }
+
+    #[test]
+    fn unsolvable_game() {
+        let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0, 0, 0, 0]]);
+        assert!(search(&game, &ExtraBlocks).is_some());
+        let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1]]);
+        assert_eq!(search(&game, &ExtraBlocks), None);
+    }
+
+    #[test]
+    fn depth_limit() {
+        let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]);
+        let limits = Limits {
+            max_depth: Some(6),
+            ..Limits::default()
+        };
+        let aborted = super::search(&game, &ExtraBlocks, &mut Budget::new(&limits));
+        assert_eq!(aborted, Err(Limit::Depth));
+    }
+}
@@ -0,0 +1,388 @@
+use std::fmt::{self, Write};
+
+use crate::{Color, Limit, Palette, Pour, Puzzle, Solution, SolveOutcome, Stats};
+
+/// A puzzle and how solving it went, written out as JSON for other programs
+/// to read, such as:
+///
+/// ```text
+/// {
+///   "puzzle": {
+///     "level": 1, "capacity": 4,
+///     "tubes": [{"id": 1, "capacity": 4, "colors": [1, 2, 1, 2]}, ...],
+///     "names": {"1": "R", "2": "G"}
+///   },
+///   "outcome": "solved", "limit": null, "optimal": true, "depth": 7,
+///   "solutions": [[{"from": 1, "to": 3, "amount": 1, "color": 1}, ...]],
+///   "stats": {
+///     "expanded": 20, "visited": 25, "peak_frontier": 6, "depth": 6,
+///     "elapsed_seconds": 0.0001,
+///     "pruned": {"swaps": 0, "finished": 2, "whole_blocks": 0}
+///   }
+/// }
+/// ```
+///
+/// Slots are listed top to bottom with 0 for empty, as in the text format.
+/// `names` holds the palette name of each color in the game that has one.
+/// The `outcome` is `solved`, `unsolvable` or `aborted`, and an aborted
+/// solve gives the `limit` that stopped it: `states`, `depth`, `deadline`,
+/// `cancelled` or `io`. `optimal` and `depth` are `null` without a solution.
+///
+/// As NDJSON the same values come one per line, each with a `type`: the
+/// `puzzle`, then a `solution` line for each solution with its `index` and
+/// `moves`, then an `outcome` line with the rest.
+///
+/// Printing a report writes the JSON.
+#[derive(Clone, Copy, Debug)]
+pub struct Report<'a> {
+    pub puzzle: &'a Puzzle,
+    pub outcome: &'a SolveOutcome,
+    /// The names given for colors, on top of their numbers.
+    pub palette: &'a Palette,
+}
+
+impl Report<'_> {
+    /// The whole report as one JSON object on a single line.
+    pub fn to_json(&self) -> String {
+        let mut json = format!("{{\"puzzle\":{}", self.puzzle_json());
+        let solutions: Vec<String> = self
+            .solutions()
+            .iter()
+            .map(|solution| self.moves_json(solution))
+            .collect();
+        write!(json, ",{}", self.outcome_json()).unwrap();
+        write!(json, ",\"solutions\":[{}]", solutions.join(",")).unwrap();
+        write!(json, ",\"stats\":{}}}", stats_json(self.outcome.stats())).unwrap();
+        json
+    }
+
+    /// The report as NDJSON, each line ending in a newline.
+    pub fn to_ndjson(&self) -> String {
+        let mut lines = format!(
+            "{{\"type\":\"puzzle\",\"puzzle\":{}}}\n",
+            self.puzzle_json()
+        );
+        for (index, solution) in self.solutions().iter().enumerate() {
+            writeln!(
+                lines,
+                "{{\"type\":\"solution\",\"index\":{index},\"moves\":{}}}",
+                self.moves_json(solution)
+            )
+            .unwrap();
+        }
+        writeln!(
+            lines,
+            "{{\"type\":\"outcome\",{},\"stats\":{}}}",
+            self.outcome_json(),
+            stats_json(self.outcome.stats())
+        )
+        .unwrap();
+        lines
+    }
+
+    fn solutions(&self) -> &[Solution] {
+        match self.outcome {
+            SolveOutcome::Solved { solutions, .. } => solutions,
+            _ => &[],
+        }
+    }
+
+    fn puzzle_json(&self) -> String {
+        let mut tubes = self.puzzle.game.state.clone();
+        tubes.sort_by_key(|tube| tube.id());
+        let tubes: Vec<String> = tubes
+            .iter()
+            .map(|tube| {
+                let colors: Vec<String> = tube.colors().iter().map(color_json).collect();
+                format!(
+                    "{{\"id\":{},\"capacity\":{},\"colors\":[{}]}}",
+                    tube.id(),
+                    tube.colors().len(),
+                    colors.join(",")
+                )
+            })
+            .collect();
+        let mut colors: Vec<Color> = self
+            .puzzle
+            .game
+            .state
+            .iter()
+            .flat_map(|tube| tube.colors())
+            .filter(|color| color.is_value())
+            .copied()
+            .collect();
+        colors.sort();
+        colors.dedup();
+        let names: Vec<String> = colors
+            .into_iter()
+            .filter_map(|color| {
+                let name = self.palette.name(color)?;
+                Some(format!("\"{}\":{}", color_json(&color), string(name)))
+            })
+            .collect();
+        format!(
+            "{{\"level\":{},\"capacity\":{},\"tubes\":[{}],\"names\":{{{}}}}}",
+            option(self.puzzle.level),
+            option(self.puzzle.capacity),
+            tubes.join(","),
+            names.join(",")
+        )
+    }
+
+    fn moves_json(&self, solution: &Solution) -> String {
+        let moves: Vec<String> = Pour::replay(&self.puzzle.game, solution)
+            .iter()
+            .map(|pour| {
+                format!(
+                    "{{\"from\":{},\"to\":{},\"amount\":{},\"color\":{}}}",
+                    pour.from,
+                    pour.to,
+                    pour.amount,
+                    color_json(&pour.color)
+                )
+            })
+            .collect();
+        format!("[{}]", moves.join(","))
+    }
+
+    /// The `outcome`, `limit`, `optimal` and `depth` fields.
+    fn outcome_json(&self) -> String {
+        let (outcome, limit, optimal, depth) = match self.outcome {
+            SolveOutcome::Solved { optimal, depth, .. } => {
+                ("solved", None, Some(*optimal), Some(*depth))
+            }
+            SolveOutcome::Unsolvable { .. } => ("unsolvable", None, None, None),
+            SolveOutcome::Aborted { limit, .. } => {
+                ("aborted", Some(limit_name(*limit)), None, None)
+            }
+        };
+        format!(
+            "\"outcome\":\"{outcome}\",\"limit\":{},\"optimal\":{},\"depth\":{}",
+            option(limit.map(string)),
+            option(optimal),
+            option(depth)
+        )
+    }
+}
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_json())
+    }
+}
+
+fn stats_json(stats: &Stats) -> String {
+    format!(
+        "{{\"expanded\":{},\"visited\":{},\"peak_frontier\":{},\"depth\":{},\
+         \"elapsed_seconds\":{},\"pruned\":{{\"swaps\":{},\"finished\":{},\"whole_blocks\":{}}}}}",
+        stats.expanded,
+        stats.visited,
+        stats.peak_frontier,
+        stats.depth,
+        stats.elapsed.as_secs_f64(),
+        stats.pruned.swaps,
+        stats.pruned.finished,
+        stats.pruned.whole_blocks
+    )
+}
+
+fn limit_name(limit: Limit) -> &'static str {
+    match limit {
+        Limit::States => "states",
+        Limit::Depth => "depth",
+        Limit::Deadline => "deadline",
+        Limit::Cancelled => "cancelled",
+        Limit::Io(_) => "io",
+    }
+}
+
+fn color_json(color: &Color) -> String {
+    match color {
+        Color::Empty => "0".to_owned(),
+        Color::Full(number) => number.to_string(),
+    }
+}
+
+fn option(value: Option<impl fmt::Display>) -> String {
+    value.map_or("null".to_owned(), |value| value.to_string())
+}
+
+/// `text` as a JSON string, quoted and escaped.
+fn string(text: &str) -> String {
+    let mut json = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if (c as u32) < 0x20 => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{Game, Pruned};
+
+    fn puzzle() -> Puzzle {
+        Puzzle::parse("level: 1\ncapacity: 2\n1 2\n2 1\n.").unwrap()
+    }
+
+    fn stats() -> Stats {
+        Stats {
+            expanded: 4,
+            visited: 6,
+            peak_frontier: 2,
+            depth: 2,
+            elapsed: Duration::from_millis(250),
+            pruned: Pruned {
+                swaps: 1,
+                finished: 0,
+                whole_blocks: 0,
+            },
+        }
+    }
+
+    const PUZZLE: &str = concat!(
+        r#"{"level":1,"capacity":2,"tubes":["#,
+        r#"{"id":1,"capacity":2,"colors":[1,2]},"#,
+        r#"{"id":2,"capacity":2,"colors":[2,1]},"#,
+        r#"{"id":3,"capacity":2,"colors":[0,0]}],"#,
+        r#""names":{"1":"R","2":"a\"b\\c"}}"#,
+    );
+
+    const STATS: &str = concat!(
+        r#"{"expanded":4,"visited":6,"peak_frontier":2,"depth":2,"elapsed_seconds":0.25,"#,
+        r#""pruned":{"swaps":1,"finished":0,"whole_blocks":0}}"#,
+    );
+
+    fn report(outcome: &SolveOutcome) -> (String, String) {
+        let puzzle = puzzle();
+        // Color 5 has a name but isn't in the game, so it isn't listed
+        let palette = Palette::new()
+            .with(1, ["R"])
+            .with(2, ["a\"b\\c"])
+            .with(5, ["pink"]);
+        let report = Report {
+            puzzle: &puzzle,
+            outcome,
+            palette: &palette,
+        };
+        (report.to_json(), report.to_ndjson())
+    }
+
+    #[test]
+    fn writes_solved() {
+        let outcome = SolveOutcome::Solved {
+            solutions: vec![vec![(1, 3), (2, 1), (2, 3)], vec![(2, 3), (1, 2), (1, 3)]],
+            depth: 3,
+            optimal: true,
+            stats: stats(),
+        };
+        let first = concat!(
+            r#"[{"from":1,"to":3,"amount":1,"color":1},"#,
+            r#"{"from":2,"to":1,"amount":1,"color":2},"#,
+            r#"{"from":2,"to":3,"amount":1,"color":1}]"#,
+        );
+        let second = concat!(
+            r#"[{"from":2,"to":3,"amount":1,"color":2},"#,
+            r#"{"from":1,"to":2,"amount":1,"color":1},"#,
+            r#"{"from":1,"to":3,"amount":1,"color":2}]"#,
+        );
+        let outcome_fields = r#""outcome":"solved","limit":null,"optimal":true,"depth":3"#;
+        let (json, ndjson) = report(&outcome);
+        assert_eq!(
+            json,
+            format!(
+                r#"{{"puzzle":{PUZZLE},{outcome_fields},"solutions":[{first},{second}],"stats":{STATS}}}"#
+            )
+        );
+        assert_eq!(
+            ndjson,
+            format!(
+                "{}\n{}\n{}\n{}\n",
+                format_args!(r#"{{"type":"puzzle","puzzle":{PUZZLE}}}"#),
+                format_args!(r#"{{"type":"solution","index":0,"moves":{first}}}"#),
+                format_args!(r#"{{"type":"solution","index":1,"moves":{second}}}"#),
+                format_args!(r#"{{"type":"outcome",{outcome_fields},"stats":{STATS}}}"#),
+            )
+        );
+    }
+
+    #[test]
+    fn writes_unsolvable() {
+        let outcome = SolveOutcome::Unsolvable { stats: stats() };
+        let outcome_fields = r#""outcome":"unsolvable","limit":null,"optimal":null,"depth":null"#;
+        let (json, ndjson) = report(&outcome);
+        assert_eq!(
+            json,
+            format!(r#"{{"puzzle":{PUZZLE},{outcome_fields},"solutions":[],"stats":{STATS}}}"#)
+        );
+        assert_eq!(
+            ndjson,
+            format!(
+                "{}\n{}\n",
+                format_args!(r#"{{"type":"puzzle","puzzle":{PUZZLE}}}"#),
+                format_args!(r#"{{"type":"outcome",{outcome_fields},"stats":{STATS}}}"#),
+            )
+        );
+    }
+
+    #[test]
+    fn writes_aborted() {
+        let outcome = SolveOutcome::Aborted {
+            limit: Limit::States,
+            stats: stats(),
+        };
+        let outcome_fields = r#""outcome":"aborted","limit":"states","optimal":null,"depth":null"#;
+        let (json, ndjson) = report(&outcome);
+        assert_eq!(
+            json,
+            format!(r#"{{"puzzle":{PUZZLE},{outcome_fields},"solutions":[],"stats":{STATS}}}"#)
+        );
+        assert_eq!(
+            ndjson,
+            format!(
+                "{}\n{}\n",
+                format_args!(r#"{{"type":"puzzle","puzzle":{PUZZLE}}}"#),
+                format_args!(r#"{{"type":"outcome",{outcome_fields},"stats":{STATS}}}"#),
+            )
+        );
+    }
+
+    #[test]
+    fn writes_puzzles_without_metadata() {
+        let puzzle = Puzzle::from(Game::new(vec![[1, 1], [0, 0]]));
+        let outcome = SolveOutcome::Unsolvable {
+            stats: Stats::default(),
+        };
+        let json = Report {
+            puzzle: &puzzle,
+            outcome: &outcome,
+            palette: &Palette::new(),
+        }
+        .to_json();
+        assert_eq!(
+            json,
+            concat!(
+                r#"{"puzzle":{"level":null,"capacity":null,"tubes":["#,
+                r#"{"id":1,"capacity":2,"colors":[1,1]},{"id":2,"capacity":2,"colors":[0,0]}],"#,
+                r#""names":{}},"#,
+                r#""outcome":"unsolvable","limit":null,"optimal":null,"depth":null,"#,
+                r#""solutions":[],"stats":{"expanded":0,"visited":0,"peak_frontier":0,"#,
+                r#""depth":0,"elapsed_seconds":0,"#,
+                r#""pruned":{"swaps":0,"finished":0,"whole_blocks":0}}}"#,
+            )
+        );
+    }
+
+    #[test]
+    fn escapes_strings() {
+        assert_eq!(string("a\"b\\c\n"), "\"a\\\"b\\\\c\\u000a\"");
+    }
+}
@@ -1,9 +1,48 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     fmt,
     hash::Hash,
+    iter,
 };
 
+use certificate::Proof;
+use search::{Budget, Expansion};
+use store::Store;
+
+mod astar;
+mod bidirectional;
+mod certificate;
+mod dfs;
+mod disk;
+mod error;
+mod heuristic;
+mod ida;
+mod json;
+mod observer;
+mod packed;
+mod palette;
+mod parallel;
+mod prune;
+mod puzzle;
+mod search;
+mod store;
+mod verify;
+
+pub use certificate::Certificate;
+pub use disk::Disk;
+pub use error::{
+    CertificateError, MoveError, MoveErrorKind, ParseError, ParseErrorKind, ValidationError,
+};
+pub use heuristic::{Boundaries, ExtraBlocks, Heuristic};
+pub use json::Report;
+pub use observer::{Progress, SolverObserver};
+pub use packed::PackedGame;
+pub use palette::Palette;
+pub use prune::{Pruned, Pruning};
+pub use puzzle::Puzzle;
+pub use search::{CancelToken, Limit, Limits, SolveOutcome, Stats};
+pub use verify::{verify_solution, Pour, Replay};
+
 #[derive(Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Color {
     Empty,
@@ -39,43 +78,65 @@ impl fmt::Debug for Color {
     }
 }
 
+/// The most units a single tube can hold.
+pub const MAX_CAPACITY: usize = 8;
+
 #[derive(Clone, Copy, Eq, PartialOrd, Ord)]
 pub struct Tube {
-    colors: [Color; 4],
+    colors: [Color; MAX_CAPACITY],
+    capacity: usize,
     id: usize,
 }
 
 impl Hash for Tube {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.colors.hash(state);
+        self.colors().hash(state);
     }
 }
 impl PartialEq for Tube {
     fn eq(&self, other: &Self) -> bool {
-        self.colors.eq(&other.colors)
+        self.colors().eq(other.colors())
     }
 }
 
 impl Tube {
-    pub fn new(number_list: [u8; 4], id: usize) -> Tube {
-        let color_list: [Color; 4] = number_list
-            .iter()
-            .map(|num| Color::new(*num))
-            .collect::<Vec<Color>>()
-            .as_slice()
-            .try_into()
-            .expect("Unable to create tube");
-        Tube {
-            colors: color_list,
-            id,
+    /// Creates a tube listed top to bottom. The length of `number_list` is
+    /// the capacity of the tube, which has to be from 1 to [`MAX_CAPACITY`].
+    pub fn new(number_list: impl AsRef<[u8]>, id: usize) -> Result<Tube, ValidationError> {
+        let number_list = number_list.as_ref();
+        let capacity = number_list.len();
+        if !(1..=MAX_CAPACITY).contains(&capacity) {
+            return Err(ValidationError::BadCapacity { tube: id, capacity });
         }
+        let mut colors = [Color::Empty; MAX_CAPACITY];
+        for (color, num) in colors.iter_mut().zip(number_list) {
+            *color = Color::new(*num);
+        }
+        Ok(Tube {
+            colors,
+            capacity,
+            id,
+        })
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// The slots of the tube, top to bottom.
+    pub fn colors(&self) -> &[Color] {
+        &self.colors[..self.capacity]
     }
 
     fn pour(&mut self, tube: &mut Tube) -> bool {
         // println!("{:?}\n{:?}", self, tube);
         let mut output = false;
         let mut color = Color::Empty;
-        'outer: for i in 0..4 {
+        'outer: for i in 0..self.capacity {
             if color.is_empty() && self.colors[i].is_value() {
                 color = self.colors[i];
                 // println!("color is {:?}", color);
@@ -85,8 +146,9 @@ impl Tube {
                     // println!("color ({:?}) != self ({:?})", color, self.colors[i]);
                     return output;
                 }
-                for o in 0..4 {
-                    if tube.colors[o].is_empty() && (tube[o + 1] == color || o == 3) {
+                for o in 0..tube.capacity {
+                    if tube.colors[o].is_empty() && (tube[o + 1] == color || o + 1 == tube.capacity)
+                    {
                         output = true;
                         // println!("Swap {i} {o}");
                         tube.colors[o] = self[i];
@@ -101,8 +163,61 @@ impl Tube {
         output
     }
 
+    /// Drops every color to the bottom of the tube, keeping their order.
+    fn settle(&mut self) {
+        let mut settled = [Color::Empty; MAX_CAPACITY];
+        let mut slot = self.capacity;
+        for color in self.colors().iter().rev().filter(|color| color.is_value()) {
+            slot -= 1;
+            settled[slot] = *color;
+        }
+        self.colors = settled;
+    }
+
+    /// The color on top of the liquid, or empty if there is none.
+    fn top(&self) -> Color {
+        self.colors()
+            .iter()
+            .find(|color| color.is_value())
+            .copied()
+            .unwrap_or(Color::Empty)
+    }
+
+    /// The number of units in the block of one color on top of the tube.
+    fn top_block(&self) -> usize {
+        let mut liquid = self.colors().iter().skip_while(|color| color.is_empty());
+        match liquid.next() {
+            Some(top) => 1 + liquid.take_while(|color| *color == top).count(),
+            None => 0,
+        }
+    }
+
+    /// How many more units fit in the tube.
+    fn room(&self) -> usize {
+        self.colors()
+            .iter()
+            .take_while(|color| color.is_empty())
+            .count()
+    }
+
+    /// A cheap hash of the colors, which unlike [`Hash`] doesn't need a
+    /// hasher.
+    fn key(&self) -> u64 {
+        self.colors()
+            .iter()
+            .fold(self.capacity as u64, |key, color| {
+                let code = match color {
+                    Color::Empty => 0,
+                    Color::Full(number) => u64::from(*number) + 1,
+                };
+                (key.rotate_left(9) ^ code).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+            })
+    }
+
+    /// A tube is uniform when it is empty or completely full of one color,
+    /// whatever its height.
     fn is_uniform(&self) -> bool {
-        for color in &self.colors[1..] {
+        for color in &self.colors()[1..] {
             if color != &self.colors[0] {
                 return false;
             }
@@ -113,61 +228,288 @@ impl Tube {
 
 impl fmt::Debug for Tube {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_list().entries(self.colors).finish()
+        f.debug_list().entries(self.colors()).finish()
     }
 }
 
 impl core::ops::Index<usize> for Tube {
     type Output = Color;
     fn index(&self, index: usize) -> &Self::Output {
-        if index < 4 {
+        if index < self.capacity {
             return &self.colors[index];
         }
         &Color::Empty
     }
 }
 
-#[derive(Clone)]
+/// How to treat liquid that was entered above an empty slot.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Gravity {
+    /// Reject the game with [`ValidationError::FloatingLiquid`].
+    Strict,
+    /// Let the liquid fall to the bottom of its tube.
+    #[default]
+    Settle,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct Game {
     size: usize,
     pub state: Vec<Tube>,
-    pub moves: Vec<(usize, usize)>,
 }
 
 impl Game {
-    pub fn new(number_state: Vec<[u8; 4]>) -> Game {
+    /// Creates a game where every tube holds `N` units. Each tube is listed
+    /// top to bottom and tube ids start at 1. `N` is checked to be from 1 to
+    /// [`MAX_CAPACITY`] when compiling.
+    pub fn new<const N: usize>(number_state: Vec<[u8; N]>) -> Game {
+        const {
+            assert!(
+                N >= 1 && N <= MAX_CAPACITY,
+                "Tubes hold 1 to MAX_CAPACITY units"
+            )
+        };
+        Game::mixed(number_state.iter().map(|nums| nums.to_vec()).collect())
+            .expect("The capacity is checked when compiling")
+    }
+
+    /// Creates a game whose tubes can hold different amounts, such as a tall
+    /// bonus tube or a short helper tube. Each tube is listed top to bottom
+    /// and the length of its list is its capacity, which has to be from 1 to
+    /// [`MAX_CAPACITY`].
+    pub fn mixed(number_state: Vec<Vec<u8>>) -> Result<Game, ValidationError> {
         let size: usize = number_state.len();
-        let game_state: Vec<Tube> = number_state
+        let game_state = number_state
             .iter()
             .enumerate()
-            .map(|(index, nums)| Tube::new(*nums, index + 1))
-            .collect();
-        Game {
+            .map(|(index, nums)| Tube::new(nums, index + 1))
+            .collect::<Result<Vec<Tube>, _>>()?;
+        Ok(Game {
             state: game_state,
             size,
-            moves: vec![],
+        })
+    }
+
+    /// Creates a game with `colors` colors, each enough to fill one tube of
+    /// `capacity`, mixed up by `seed`, and `empty` more empty tubes. The same
+    /// seed always gives the same game, which may not be solvable.
+    pub fn shuffled(
+        colors: u8,
+        capacity: usize,
+        empty: usize,
+        seed: u64,
+    ) -> Result<Game, ValidationError> {
+        if !(1..=MAX_CAPACITY).contains(&capacity) {
+            return Err(ValidationError::BadCapacity { tube: 1, capacity });
         }
+        let mut units: Vec<u8> = (1..=colors)
+            .flat_map(|color| iter::repeat_n(color, capacity))
+            .collect();
+        let mut state = seed;
+        for i in (1..units.len()).rev() {
+            state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let j = certificate::mix(state) % (i as u64 + 1);
+            units.swap(i, j as usize);
+        }
+        let mut tubes: Vec<Vec<u8>> = units.chunks(capacity).map(<[u8]>::to_vec).collect();
+        tubes.extend(iter::repeat_n(vec![0; capacity], empty));
+        Game::mixed(tubes)
     }
 
-    fn check_valid(&self) {
-        let mut color_count: HashMap<&Color, usize> = HashMap::new();
+    /// Adds `count` empty tubes holding `capacity` each, numbered after the
+    /// highest tube id.
+    pub fn add_empty_tubes(
+        &mut self,
+        count: usize,
+        capacity: usize,
+    ) -> Result<(), ValidationError> {
+        let last = self.state.iter().map(Tube::id).max().unwrap_or(0);
+        for id in last + 1..=last + count {
+            self.state.push(Tube::new(vec![0; capacity], id)?);
+        }
+        self.size = self.state.len();
+        Ok(())
+    }
+
+    /// The number of units each tube holds, or `None` if the tubes differ.
+    pub fn capacity(&self) -> Option<usize> {
+        let capacity = self.state.first()?.capacity;
+        self.state
+            .iter()
+            .all(|tube| tube.capacity == capacity)
+            .then_some(capacity)
+    }
+
+    /// Checks that the game can be solved as entered, returning the first of
+    /// its [`problems`](Game::problems).
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        match self.problems().into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Lists everything wrong with the unit counts of the game. A game is
+    /// solved once every tube is empty or full of one color, so each color has
+    /// to fill some tubes of its own: a color with `n` units needs tubes that
+    /// hold `n` between them, such as one tube of 4 or two of 2, and no tube
+    /// can go to two colors. Tubes that no color fills end up empty, so the
+    /// empty slots have to add up to what some set of tubes holds too.
+    ///
+    /// A color whose units no set of tubes holds is reported as too few or too
+    /// many, and empty slots as the wrong number, against whichever amount
+    /// some tubes do hold is nearest.
+    pub fn problems(&self) -> Vec<ValidationError> {
+        if self.state.is_empty() {
+            return vec![ValidationError::NoTubes];
+        }
+        let mut color_count: BTreeMap<Color, (usize, Vec<usize>)> = BTreeMap::new();
+        let mut capacity_tubes: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
         for tube in &self.state {
-            for color in &tube.colors {
-                let count = color_count.entry(color).or_insert(0);
+            capacity_tubes
+                .entry(tube.capacity)
+                .or_default()
+                .push(tube.id);
+            for color in tube.colors() {
+                let (count, tubes) = color_count.entry(*color).or_default();
                 *count += 1;
+                if tubes.last() != Some(&tube.id) {
+                    tubes.push(tube.id);
+                }
+            }
+        }
+        let (empty, empty_tubes) = color_count.remove(&Color::Empty).unwrap_or_default();
+        let total: usize = self.state.iter().map(Tube::capacity).sum();
+        // Whether some set of tubes holds each amount up to all of them
+        let mut sums = vec![false; total + 1];
+        sums[0] = true;
+        for tube in &self.state {
+            for sum in (tube.capacity..=total).rev() {
+                sums[sum] |= sums[sum - tube.capacity];
             }
         }
-        for (&&color, &count) in &color_count {
-            match color {
-                Color::Empty if count % 4 != 0 => panic!("Invalid Number of Empty colors: {count}"),
-                Color::Full(_) if count != 4 => {
-                    panic!("Invalid Number of Colors: {:#?}", color_count)
+
+        // The amount some set of tubes holds that is nearest to `count`, at
+        // least `least`, preferring the larger one on a tie
+        let nearest = |count: usize, least: usize| {
+            let below = (least..count.min(total + 1)).rev().find(|&sum| sums[sum]);
+            let above = (count + 1..=total).find(|&sum| sums[sum]);
+            match (below, above) {
+                (Some(below), Some(above)) if count - below < above - count => below,
+                (_, Some(above)) => above,
+                (below, None) => below.expect("All the tubes together hold something"),
+            }
+        };
+
+        let mut problems = vec![];
+        let mut counts = vec![];
+        for (color, (count, tubes)) in color_count {
+            if count <= total && sums[count] {
+                counts.push((color, count));
+                continue;
+            }
+            let expected = nearest(count, 1);
+            problems.push(if expected < count {
+                ValidationError::TooMany {
+                    color,
+                    count,
+                    expected,
+                    tubes,
                 }
-                _ => (),
+            } else {
+                ValidationError::TooFew {
+                    color,
+                    count,
+                    expected,
+                    tubes,
+                }
+            });
+        }
+        if !sums[empty] {
+            problems.push(ValidationError::EmptySlots {
+                found: empty,
+                expected: nearest(empty, 0),
+                tubes: empty_tubes,
+            });
+        }
+        if problems.is_empty() {
+            problems.extend(self.arrangement_problem(&counts, &capacity_tubes));
+        }
+        problems
+    }
+
+    /// Why the colors, each with a number of units some tubes hold, can't all
+    /// fill tubes of their own at once, if they can't.
+    fn arrangement_problem(
+        &self,
+        counts: &[(Color, usize)],
+        capacity_tubes: &BTreeMap<usize, Vec<usize>>,
+    ) -> Option<ValidationError> {
+        let mut units: Vec<usize> = counts.iter().map(|&(_, count)| count).collect();
+        units.sort_unstable_by(|a, b| b.cmp(a));
+        let mut free: Vec<(usize, usize)> = capacity_tubes
+            .iter()
+            .map(|(&capacity, tubes)| (capacity, tubes.len()))
+            .collect();
+        if arrange(&units, &mut free, &mut HashSet::new()) {
+            return None;
+        }
+        // Blame a size of tube that more colors need exactly than there are
+        let mut claimed: BTreeMap<usize, Vec<Color>> = BTreeMap::new();
+        for &(color, count) in counts {
+            if capacity_tubes.contains_key(&count) {
+                claimed.entry(count).or_default().push(color);
             }
         }
+        for (capacity, colors) in claimed {
+            let tubes = &capacity_tubes[&capacity];
+            if colors.len() > tubes.len() {
+                return Some(ValidationError::NotEnoughTubes {
+                    capacity,
+                    colors,
+                    tubes: tubes.clone(),
+                });
+            }
+        }
+        Some(ValidationError::NoArrangement {
+            colors: counts.iter().map(|&(color, _)| color).collect(),
+        })
+    }
+
+    /// Checks that no liquid floats above an empty slot. Floating liquid is
+    /// dealt with by [`normalize`](Game::normalize), so
+    /// [`validate`](Game::validate) allows it.
+    pub fn check_settled(&self) -> Result<(), ValidationError> {
+        for tube in &self.state {
+            for pair in tube.colors().windows(2) {
+                if pair[0].is_value() && pair[1].is_empty() {
+                    return Err(ValidationError::FloatingLiquid {
+                        color: pair[0],
+                        tube: tube.id,
+                    });
+                }
+            }
+        }
+        Ok(())
     }
 
+    /// Deals with liquid entered above an empty slot. Tubes keep their ids
+    /// and positions, so moves found for the normalized game use the same
+    /// ids as the game that was entered.
+    pub fn normalize(&mut self, gravity: Gravity) -> Result<(), ValidationError> {
+        match gravity {
+            Gravity::Strict => self.check_settled(),
+            Gravity::Settle => {
+                for tube in self.state.iter_mut() {
+                    tube.settle();
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Pours the top color of tube `a` into tube `b`. Both tubes are expected
+    /// to be settled, see [`normalize`](Game::normalize).
     pub fn pour(&mut self, a: usize, b: usize) -> bool {
         if a == b {
             return false;
@@ -177,34 +519,72 @@ impl Game {
         self.state[b] = tube;
         output
     }
-    fn try_pour(&self, a: usize, b: usize) -> Option<Game> {
-        {
-            if a == b {
-                return None;
-            }
-            let mut tx = self.state[a].clone();
-            let mut rx = self.state[b].clone();
-            if !tx.pour(&mut rx) {
-                return None;
-            }
-            let mut game = self.clone();
-            game.state[a] = tx;
-            game.state[b] = rx;
-            Some(game)
+    /// The tubes `a` and `b` would become after pouring from `a` into `b`.
+    fn try_pour(&self, a: usize, b: usize) -> Option<(Tube, Tube)> {
+        if a == b {
+            return None;
         }
+        let mut tx = self.state[a];
+        let mut rx = self.state[b];
+        tx.pour(&mut rx).then_some((tx, rx))
     }
 
-    fn moves(&self) -> Vec<Game> {
-        let mut games = Vec::new();
+    /// Every game one pour away, each with the tube ids poured from and into.
+    /// Pours that lead to the same game are only listed once, and pours that
+    /// `pruning` rules out are left out and counted in `pruned`.
+    fn moves(&self, pruning: &Pruning, pruned: &mut Pruned) -> Vec<((usize, usize), Game)> {
+        // A game hashes to the sum of its tube hashes, so a pour changes the
+        // hash by the two tubes it touches, whatever order the tubes are in.
+        // Two pours give the same game exactly when the tubes each one leaves
+        // behind, together with the tubes the other one takes away, match.
+        let keys: Vec<u64> = self.state.iter().map(Tube::key).collect();
+        let tops: Vec<(Color, usize)> = self
+            .state
+            .iter()
+            .map(|tube| (tube.top(), tube.room()))
+            .collect();
+        let sorted = self.state.is_sorted();
+        // The changes made so far, by the hash of the game they give
+        let mut seen: HashMap<u64, Vec<[Tube; 4]>> = HashMap::new();
+        let mut games: Vec<((usize, usize), Game)> = Vec::new();
         for i in 0..self.size {
+            let (color, _) = tops[i];
+            if color.is_empty() {
+                continue;
+            }
             for o in 0..self.size {
-                if let Some(mut game) = self.try_pour(i, o) {
-                    game.moves.push((game.state[i].id, game.state[o].id));
+                // Only pour onto the same color or into an empty tube
+                let (top, room) = tops[o];
+                if room == 0 || (top.is_value() && top != color) {
+                    continue;
+                }
+                let Some((tx, rx)) = self.try_pour(i, o) else {
+                    continue;
+                };
+                if let Some(rule) = pruning.cut(self, i, o) {
+                    pruned.count(rule);
+                    continue;
+                }
+                let key = tx
+                    .key()
+                    .wrapping_add(rx.key())
+                    .wrapping_sub(keys[i])
+                    .wrapping_sub(keys[o]);
+                let change = [tx, rx, self.state[i], self.state[o]];
+                let same = seen.entry(key).or_default();
+                if same.iter().any(|other| same_change(&change, other)) {
+                    continue;
+                }
+                same.push(change);
+                let mut game = self.clone();
+                game.state[i] = tx;
+                game.state[o] = rx;
+                if sorted {
+                    game.resort(i, o);
+                } else {
                     game.sort();
-                    if !games.contains(&game) {
-                        games.push(game);
-                    }
                 }
+                games.push(((tx.id, rx.id), game));
             }
         }
         games
@@ -214,6 +594,110 @@ impl Game {
         self.state.sort_unstable();
     }
 
+    /// Puts the tubes at `a` and `b` back in order, the others being sorted
+    /// already.
+    fn resort(&mut self, a: usize, b: usize) {
+        let later = self.state.remove(a.max(b));
+        let earlier = self.state.remove(a.min(b));
+        for tube in [later, earlier] {
+            let at = self.state.partition_point(|other| *other < tube);
+            self.state.insert(at, tube);
+        }
+    }
+
+    /// Renames the colors so that games differing only by color names, such
+    /// as every 3 swapped with every 7, usually come out the same. Colors are
+    /// numbered from 1 as they are met, taking next the tube that reads first
+    /// under the names given so far. A color not named yet reads as where its
+    /// units sit, so only colors that sit alike can make two such games
+    /// differ. Tubes keep their ids, so moves found for the renamed game solve
+    /// the original one too.
+    ///
+    /// This is not a full canonical form: it merges most games that differ by
+    /// color names but not all of them, and a game it misses is counted as a
+    /// game of its own. Searches with [`Solver::with_symmetry`] still find the
+    /// shortest solutions, but how many games they check, and the number of
+    /// games a [`Certificate`] counts, depend on which games get merged.
+    pub fn canonicalize(&mut self) {
+        // Ties below go to the earlier tube, so start from the same order
+        // whatever order the tubes came in
+        self.sort();
+        // Rank the colors by the heights and slots their units sit in
+        let mut places: BTreeMap<u8, Vec<(usize, usize)>> = BTreeMap::new();
+        for tube in &self.state {
+            for (slot, color) in tube.colors().iter().enumerate() {
+                if let Color::Full(number) = *color {
+                    places
+                        .entry(number)
+                        .or_default()
+                        .push((tube.capacity, slot));
+                }
+            }
+        }
+        for units in places.values_mut() {
+            units.sort_unstable();
+        }
+        let mut signatures: Vec<&Vec<(usize, usize)>> = places.values().collect();
+        signatures.sort_unstable();
+        signatures.dedup();
+        let mut ranks = [0u16; 256];
+        for (number, units) in &places {
+            ranks[*number as usize] = signatures
+                .binary_search(&units)
+                .expect("Every color has a signature") as u16;
+        }
+
+        let mut names = [0u8; 256];
+        let mut named = 0;
+        let read = |tube: &Tube, names: &[u8; 256]| {
+            let mut read = [0u16; MAX_CAPACITY];
+            let mut new = vec![];
+            for (slot, color) in read.iter_mut().zip(tube.colors()) {
+                if let Color::Full(number) = *color {
+                    *slot = match names[number as usize] {
+                        0 => {
+                            let known = new.iter().position(|&new| new == number);
+                            let seen = known.unwrap_or_else(|| {
+                                new.push(number);
+                                new.len() - 1
+                            });
+                            256 + ranks[number as usize] * MAX_CAPACITY as u16 + seen as u16
+                        }
+                        name => name.into(),
+                    };
+                }
+            }
+            (tube.capacity, read)
+        };
+        let mut left: Vec<&Tube> = self.state.iter().collect();
+        while !left.is_empty() {
+            let (next, _) = left
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, tube)| read(tube, &names))
+                .expect("Tubes are left");
+            for color in left.remove(next).colors() {
+                if let Color::Full(number) = *color {
+                    if names[number as usize] == 0 {
+                        named += 1;
+                        names[number as usize] = named;
+                    }
+                }
+            }
+        }
+        for tube in self.state.iter_mut() {
+            for color in tube.colors.iter_mut() {
+                if let Color::Full(number) = *color {
+                    *color = Color::Full(names[number as usize]);
+                }
+            }
+        }
+        self.sort();
+    }
+
+    /// A game is solved once every tube is uniform, so tall and short tubes
+    /// count as done only when they are empty or full. A color may end up
+    /// split across several tubes, as [`problems`](Game::problems) allows.
     pub fn is_solved(&self) -> bool {
         for tube in self.state.iter() {
             if !tube.is_uniform() {
@@ -223,112 +707,416 @@ impl Game {
         true
     }
 
-    pub fn tube(&self, id: usize) -> usize {
-        for (index, tube) in self.state.iter().enumerate() {
-            if tube.id == id {
-                return index;
-            }
+    /// The index in [`state`](Game::state) of the tube with id `id`.
+    pub fn tube(&self, id: usize) -> Option<usize> {
+        self.state.iter().position(|tube| tube.id == id)
+    }
+}
+
+/// Whether colors with `counts` units can each fill whole tubes of their
+/// own, taking them from `free`, the number of tubes left of each capacity.
+/// Arrangements already known not to work are kept in `failed`.
+fn arrange(
+    counts: &[usize],
+    free: &mut [(usize, usize)],
+    failed: &mut HashSet<(usize, Vec<(usize, usize)>)>,
+) -> bool {
+    let Some((&count, rest)) = counts.split_first() else {
+        return true;
+    };
+    let key = (counts.len(), free.to_vec());
+    if failed.contains(&key) {
+        return false;
+    }
+    let found = take(count, 0, rest, free, failed);
+    if !found {
+        failed.insert(key);
+    }
+    found
+}
+
+/// Takes tubes from `free[from..]` holding `left` more units between them,
+/// then arranges the `rest` of the colors in what is left.
+fn take(
+    left: usize,
+    from: usize,
+    rest: &[usize],
+    free: &mut [(usize, usize)],
+    failed: &mut HashSet<(usize, Vec<(usize, usize)>)>,
+) -> bool {
+    if left == 0 {
+        return arrange(rest, free, failed);
+    }
+    for i in from..free.len() {
+        let (capacity, available) = free[i];
+        if available == 0 || capacity > left {
+            continue;
+        }
+        free[i].1 -= 1;
+        let found = take(left - capacity, i, rest, free, failed);
+        free[i].1 += 1;
+        if found {
+            return true;
         }
-        panic!("Id Not Found {}", id);
     }
+    false
+}
+
+/// Whether two pours, each given as the two tubes it leaves and the two it
+/// takes away, give the same game. That is when the tubes one leaves and the
+/// other takes away are the same, compared by colors.
+fn same_change(a: &[Tube; 4], b: &[Tube; 4]) -> bool {
+    let mut left = [a[0], a[1], b[2], b[3]];
+    let mut right = [b[0], b[1], a[2], a[3]];
+    left.sort_unstable_by(|x, y| x.colors().cmp(y.colors()));
+    right.sort_unstable_by(|x, y| x.colors().cmp(y.colors()));
+    left.iter().zip(&right).all(|(x, y)| x == y)
 }
 
 impl fmt::Debug for Game {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "Game Size {}:\n{:?}\nMoves: {:?}",
-            self.size,
-            self.state.iter(),
-            self.moves
-        )
+        write!(f, "Game Size {}:\n{:?}", self.size, self.state.iter())
     }
 }
 
-impl Eq for Game {}
-impl PartialEq for Game {
-    fn eq(&self, other: &Self) -> bool {
-        self.size == other.size && self.state == other.state
-    }
+/// How the solver searches for solutions.
+#[derive(Debug, Default)]
+pub enum Strategy {
+    /// Expand every game one move deeper at a time, finding all of the
+    /// shortest solutions.
+    #[default]
+    Bfs,
+    /// Expand the most promising game first, finding one shortest solution
+    /// while looking at far fewer games.
+    AStar(Box<dyn Heuristic>),
+    /// Search depth first within a bound on moves made plus the estimate of
+    /// moves left, raising the bound until a solution fits. Finds one
+    /// shortest solution using memory that only grows with its length.
+    /// Proving a game unsolvable this way is slow, as the same games get
+    /// searched again every round.
+    IdaStar(Box<dyn Heuristic>),
+    /// Search depth first, trying the move the heuristic likes best first,
+    /// and stop at the first solution. Much faster on big games, but the
+    /// solution is not guaranteed to be a shortest one.
+    Dfs(Box<dyn Heuristic>),
+    /// Search breadth first from the start and backward from the solved
+    /// games at once, meeting in the middle, and find one shortest solution.
+    /// A pour can be undone in more ways than it can be made, so the
+    /// backward side branches faster and the search often looks at about as
+    /// many games as [`Strategy::Bfs`].
+    Bidirectional,
 }
 
-impl Hash for Game {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.size.hash(state);
-        self.state.hash(state);
-        // No moves bc we want to duplicate moves avoid games states
+impl Strategy {
+    /// Whether the solutions found are guaranteed to be shortest, assuming
+    /// the heuristic never overestimates.
+    pub fn is_optimal(&self) -> bool {
+        !matches!(self, Strategy::Dfs(_))
     }
 }
+
+/// The moves that solve a game, each pouring from one tube id into another.
+pub type Solution = Vec<(usize, usize)>;
+
+/// The fewest empty tubes a game needs added to be solvable, as found by
+/// [`Solver::extra_tubes`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExtraTubes {
+    /// How many empty tubes were added.
+    pub tubes: usize,
+    /// The game with the tubes added. The moves of any solution use its tube
+    /// ids.
+    pub game: Game,
+    /// How solving that game ended: solved, unsolvable if even the most tubes
+    /// allowed were not enough, or aborted by a limit.
+    pub outcome: SolveOutcome,
+}
+
+// A solver can be handed to another thread, such as off a UI thread
+fn _assert_send() {
+    fn f<T: Send>() {}
+    f::<Solver>();
+}
+
 #[derive(Debug)]
 pub struct Solver {
-    states: HashSet<Game>,
-    queue: Vec<Game>,
-    solutions: Vec<Game>,
+    game: Game,
+    strategy: Strategy,
+    limits: Limits,
+    observer: Option<Box<dyn SolverObserver>>,
+    symmetry: bool,
+    pruning: Pruning,
+    threads: usize,
+    disk: Option<Disk>,
+    states: Store,
+    /// Indices into `states` of the games left to expand at this depth.
+    queue: Vec<usize>,
+    solutions: Vec<Solution>,
 }
 
 impl Solver {
-    pub fn new(game: Game) -> Self {
+    /// Creates a solver for `game`, letting any floating liquid settle first.
+    /// Use [`Game::normalize`] with [`Gravity::Strict`] beforehand to reject
+    /// such games instead.
+    pub fn new(mut game: Game) -> Result<Self, ValidationError> {
+        game.validate()?;
+        game.normalize(Gravity::Settle)?;
         let mut solver = Solver {
-            states: HashSet::new(),
+            game: game.clone(),
+            strategy: Strategy::default(),
+            limits: Limits::default(),
+            observer: None,
+            symmetry: false,
+            pruning: Pruning::default(),
+            threads: 1,
+            disk: None,
+            states: Store::new(),
             queue: vec![],
             solutions: vec![],
         };
-        game.check_valid();
-        solver.states.insert(game.clone());
-        solver.queue.push(game);
-        solver
+        solver.queue.extend(solver.states.insert(&game, None));
+        Ok(solver)
+    }
+
+    pub fn with_strategy(mut self, strategy: Strategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn with_observer(mut self, observer: Box<dyn SolverObserver>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Treats games that only differ by color names as the same game, see
+    /// [`Game::canonicalize`]. The solutions found are just as short, but
+    /// fewer of the equally short ones are reported.
+    pub fn with_symmetry(mut self, symmetry: bool) -> Self {
+        self.symmetry = symmetry;
+        let game = self.game.clone();
+        self.restart(game);
+        self
     }
-    fn next_move(&mut self) -> bool {
+
+    /// Skips the moves `pruning` rules out. The safe rules are on by default.
+    pub fn with_pruning(mut self, pruning: Pruning) -> Self {
+        self.pruning = pruning;
+        self
+    }
+
+    /// Splits each layer of the breadth first search over `threads` threads.
+    /// The depth and solutions found are the same as on one thread, but
+    /// limits are only checked between layers. Other strategies run on one
+    /// thread.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Keeps the games the breadth first search has seen in files rather
+    /// than in memory, for games with more states than fit in memory. Finds
+    /// the same solutions, more slowly. Other strategies, and threads, are
+    /// not used with it.
+    pub fn with_disk(mut self, disk: Disk) -> Self {
+        self.disk = Some(disk);
+        self
+    }
+
+    /// Whether the solutions found are guaranteed to be shortest.
+    pub fn is_optimal(&self) -> bool {
+        self.strategy.is_optimal() && self.pruning.is_optimal()
+    }
+
+    fn next_move(&mut self, budget: &mut Budget) -> Result<bool, Limit> {
         let mut queue = vec![];
-        while let Some(game) = self.queue.pop() {
-            for new_move in game.moves() {
+        let depth = self
+            .queue
+            .first()
+            .map_or(0, |&index| self.states.depth(index));
+        if budget.too_deep(depth + 1) {
+            return Err(Limit::Depth);
+        }
+        while let Some(index) = self.queue.pop() {
+            budget.expand(depth)?;
+            let game = self.states.game(index);
+            for (step, new_move) in budget.children(&game) {
                 if new_move.is_solved() {
-                    self.solutions.push(new_move);
-                } else if self.states.insert(new_move.clone()) {
-                    queue.push(new_move);
+                    let mut solution = self.states.path(index);
+                    solution.push(step);
+                    budget.found(&solution);
+                    self.solutions.push(solution);
+                } else if let Some(new_index) = self.states.insert(&new_move, Some((index, step))) {
+                    queue.push(new_index);
                 }
             }
         }
         self.queue = queue;
+        budget.progress(depth + 1, self.queue.len(), self.states.len());
         // !self.queue.is_empty() // True if not done
-        self.solutions.is_empty() // Search until a solution is found
+        Ok(self.solutions.is_empty()) // Search until a solution is found
     }
 
-    pub fn get_solutions(&mut self) -> Option<Vec<Vec<(usize, usize)>>> {
-        while self.next_move() {
-            println!("Games to check: {}", self.queue.len());
-            if self.queue.len() == 0 {
-                println!("Game is unsolvable");
-                return None;
+    fn breadth_first(&mut self, budget: &mut Budget) -> Result<Option<Vec<Solution>>, Limit> {
+        // A search that hit a limit leaves a layer half expanded
+        self.restart(self.game.clone());
+        if self.game.is_solved() {
+            let solution = vec![];
+            budget.found(&solution);
+            return Ok(Some(vec![solution]));
+        }
+        while self.next_move(budget)? {
+            if self.queue.is_empty() {
+                return Ok(None);
             }
         }
-        // self.solutions.sort_by(|a, b| b.moves.cmp(&a.moves));
-        Some(
-            self.solutions
-                .iter()
-                .map(|solution| solution.moves.clone())
-                .collect(),
-        )
+        Ok(Some(self.solutions.clone()))
     }
 
-    pub fn solve(&mut self) {
-        let solutions = self.get_solutions();
-        let moves = if let Some(solution) = solutions.as_ref() {
-            solution[0].len()
-        } else {
-            println!("No solutions found");
-            return;
+    /// Searches for solutions, telling a game with no solution apart from
+    /// one the solver gave up on.
+    pub fn solve(&mut self) -> SolveOutcome {
+        let mut budget = Budget::new(&self.limits);
+        budget.observer = self.observer.take();
+        budget.expansion = Expansion {
+            pruning: self.pruning,
+            symmetry: self.symmetry,
         };
-        let solutions = solutions
-            .iter()
-            .map(|solution| format!("{:?}", solution))
-            .collect::<Vec<String>>();
-        println!(
-            "Done! {} Solution(s) Found with {} moves:\n{:#?}",
-            solutions.len(),
-            moves,
-            solutions
-        );
+        let game = &self.start();
+        let single = |solution: Option<Solution>| solution.map(|moves| vec![moves]);
+        let result = match &self.strategy {
+            Strategy::Bfs if self.disk.is_some() => {
+                disk::search(game, self.disk.as_ref().expect("Disk was set"), &mut budget)
+            }
+            Strategy::Bfs if self.threads > 1 => parallel::search(game, self.threads, &mut budget),
+            Strategy::Bfs => self.breadth_first(&mut budget),
+            Strategy::AStar(heuristic) => {
+                astar::search(game, heuristic.as_ref(), &mut budget).map(single)
+            }
+            Strategy::IdaStar(heuristic) => {
+                ida::search(game, heuristic.as_ref(), &mut budget).map(single)
+            }
+            Strategy::Dfs(heuristic) => {
+                dfs::search(game, heuristic.as_ref(), &mut budget).map(single)
+            }
+            Strategy::Bidirectional => bidirectional::search(game, &mut budget).map(single),
+        };
+        let optimal = self.is_optimal();
+        self.finish(budget, result, optimal)
+    }
+
+    /// Checks every game reachable from the start, and if none is solved
+    /// returns a [`Certificate`] that proves it. Otherwise returns how the
+    /// search ended instead, with a shortest solution or the limit that
+    /// stopped it. The strategy and pruning are not used, as the certificate
+    /// has to count every game.
+    pub fn certify(&mut self) -> Result<Certificate, SolveOutcome> {
+        let mut budget = Budget::new(&self.limits);
+        budget.observer = self.observer.take();
+        budget.expansion = Expansion {
+            pruning: Pruning::none(),
+            symmetry: true,
+        };
+        match certificate::search(&self.game, &mut budget) {
+            Ok(Proof::Unsolvable(certificate)) => {
+                self.finish(budget, Ok(None), true);
+                Ok(certificate)
+            }
+            // Every game is checked a move deeper at a time, so the solution
+            // is a shortest one whatever the strategy
+            Ok(Proof::Solved(solution)) => Err(self.finish(budget, Ok(Some(vec![solution])), true)),
+            Err(limit) => Err(self.finish(budget, Err(limit), true)),
+        }
+    }
+
+    /// Hands the observer back to the solver and tells it how the search
+    /// ended, and whether any solution found is a shortest one.
+    fn finish(
+        &mut self,
+        mut budget: Budget,
+        result: Result<Option<Vec<Solution>>, Limit>,
+        optimal: bool,
+    ) -> SolveOutcome {
+        let outcome = outcome(result, optimal, budget.stats());
+        self.observer = budget.observer.take();
+        if let Some(observer) = self.observer.as_mut() {
+            observer.on_finish(&outcome);
+        }
+        outcome
+    }
+
+    /// Finds the fewest empty tubes, up to `max`, that make the game solvable
+    /// once added, solving it with no extra tubes first, then one, and so on
+    /// with the solver's settings. The tubes added are as tall as the tallest
+    /// tube. Stops early if a limit is hit, as the game might have been
+    /// solvable. The solver is left set up for the game it was made with.
+    pub fn extra_tubes(&mut self, max: usize) -> ExtraTubes {
+        let original = self.game.clone();
+        let capacity = original.state.iter().map(Tube::capacity).max().unwrap_or(1);
+        let mut tubes = 0;
+        let extra = loop {
+            let mut game = original.clone();
+            game.add_empty_tubes(tubes, capacity)
+                .expect("The tallest tube has a valid capacity");
+            self.restart(game.clone());
+            let outcome = self.solve();
+            if tubes == max || !matches!(outcome, SolveOutcome::Unsolvable { .. }) {
+                break ExtraTubes {
+                    tubes,
+                    game,
+                    outcome,
+                };
+            }
+            tubes += 1;
+        };
+        self.restart(original);
+        extra
+    }
+
+    /// Points the solver at `game`, forgetting any search made so far.
+    fn restart(&mut self, game: Game) {
+        self.game = game;
+        let start = self.start();
+        self.states = Store::new();
+        self.queue = self.states.insert(&start, None).into_iter().collect();
+        self.solutions.clear();
+    }
+
+    /// The game the search starts from. With symmetry on it has its colors
+    /// renamed, while `self.game` stays the game the solver was given.
+    fn start(&self) -> Game {
+        let mut game = self.game.clone();
+        if self.symmetry {
+            game.canonicalize();
+        }
+        game
+    }
+
+    /// Searches for solutions, returning `None` if there are none or a limit
+    /// was reached first.
+    pub fn get_solutions(&mut self) -> Option<Vec<Solution>> {
+        self.solve().into_solutions()
+    }
+}
+
+fn outcome(
+    result: Result<Option<Vec<Solution>>, Limit>,
+    optimal: bool,
+    stats: Stats,
+) -> SolveOutcome {
+    match result {
+        Ok(Some(solutions)) => SolveOutcome::Solved {
+            depth: solutions[0].len(),
+            solutions,
+            optimal,
+            stats,
+        },
+        Ok(None) => SolveOutcome::Unsolvable { stats },
+        Err(limit) => SolveOutcome::Aborted { limit, stats },
     }
 }
 
@@ -375,106 +1163,150 @@ mod tests {
     #[test]
     fn tube_constructor() {
         assert_eq!(
-            Tube::new([0; 4], 0),
+            Tube::new([0; 4], 0).unwrap(),
             Tube {
-                colors: [Color::Empty; 4],
+                colors: [Color::Empty; MAX_CAPACITY],
+                capacity: 4,
                 id: 0
             }
         );
         test_many_tubes(|colors| {
             assert_eq!(
-                Tube::new(colors, 0),
+                Tube::new(colors, 0).unwrap(),
                 Tube {
                     colors: [
                         Color::new(colors[0]),
                         Color::new(colors[1]),
                         Color::new(colors[2]),
-                        Color::new(colors[3])
+                        Color::new(colors[3]),
+                        Color::Empty,
+                        Color::Empty,
+                        Color::Empty,
+                        Color::Empty
                     ],
+                    capacity: 4,
                     id: 0
                 }
             );
         });
     }
 
+    #[test]
+    #[should_panic]
+    fn tube_too_tall() {
+        Tube::new([1; MAX_CAPACITY + 1], 0).unwrap();
+    }
+
     #[test]
     fn can_pour_into_empty() {
-        let mut tube = Tube::new([1, 2, 3, 4], 0);
-        let mut empty = Tube::new([0, 0, 0, 0], 0);
+        let mut tube = Tube::new([1, 2, 3, 4], 0).unwrap();
+        let mut empty = Tube::new([0, 0, 0, 0], 0).unwrap();
         assert!(tube.pour(&mut empty));
-        assert_eq!(tube, Tube::new([0, 2, 3, 4], 0));
-        empty = Tube::new([0, 0, 0, 0], 0);
+        assert_eq!(tube, Tube::new([0, 2, 3, 4], 0).unwrap());
+        empty = Tube::new([0, 0, 0, 0], 0).unwrap();
         assert!(tube.pour(&mut empty));
-        assert_eq!(tube, Tube::new([0, 0, 3, 4], 0));
+        assert_eq!(tube, Tube::new([0, 0, 3, 4], 0).unwrap());
     }
 
     #[test]
     fn can_pour_into_partialy_full() {
-        let mut tube = Tube::new([1, 2, 3, 4], 0);
-        let mut partial = Tube::new([0, 1, 1, 1], 0);
+        let mut tube = Tube::new([1, 2, 3, 4], 0).unwrap();
+        let mut partial = Tube::new([0, 1, 1, 1], 0).unwrap();
         assert!(tube.pour(&mut partial));
-        assert_eq!(tube, Tube::new([0, 2, 3, 4], 0));
-        assert_eq!(partial, Tube::new([1, 1, 1, 1], 0));
+        assert_eq!(tube, Tube::new([0, 2, 3, 4], 0).unwrap());
+        assert_eq!(partial, Tube::new([1, 1, 1, 1], 0).unwrap());
     }
 
     #[test]
     fn can_pour_multiple() {
-        let mut tube = Tube::new([1, 1, 2, 3], 0);
-        let mut into = Tube::new([0, 0, 1, 1], 0);
+        let mut tube = Tube::new([1, 1, 2, 3], 0).unwrap();
+        let mut into = Tube::new([0, 0, 1, 1], 0).unwrap();
         assert!(tube.pour(&mut into));
-        assert_eq!(tube, Tube::new([0, 0, 2, 3], 0));
-        assert_eq!(into, Tube::new([1, 1, 1, 1], 0));
+        assert_eq!(tube, Tube::new([0, 0, 2, 3], 0).unwrap());
+        assert_eq!(into, Tube::new([1, 1, 1, 1], 0).unwrap());
     }
 
     #[test]
     fn cant_pour_underneath() {
-        let mut tube = Tube::new([1, 1, 2, 1], 0);
-        let mut into = Tube::new([0, 0, 0, 0], 0);
+        let mut tube = Tube::new([1, 1, 2, 1], 0).unwrap();
+        let mut into = Tube::new([0, 0, 0, 0], 0).unwrap();
         assert!(tube.pour(&mut into));
-        assert_eq!(tube, Tube::new([0, 0, 2, 1], 0));
-        assert_eq!(into, Tube::new([0, 0, 1, 1], 0));
+        assert_eq!(tube, Tube::new([0, 0, 2, 1], 0).unwrap());
+        assert_eq!(into, Tube::new([0, 0, 1, 1], 0).unwrap());
     }
 
     #[test]
     fn pours_partial() {
-        let mut tube = Tube::new([1, 1, 2, 3], 0);
-        let mut into = Tube::new([0, 1, 2, 3], 0);
+        let mut tube = Tube::new([1, 1, 2, 3], 0).unwrap();
+        let mut into = Tube::new([0, 1, 2, 3], 0).unwrap();
         assert!(tube.pour(&mut into));
-        assert_eq!(tube, Tube::new([0, 1, 2, 3], 0));
-        assert_eq!(into, Tube::new([1, 1, 2, 3], 0));
+        assert_eq!(tube, Tube::new([0, 1, 2, 3], 0).unwrap());
+        assert_eq!(into, Tube::new([1, 1, 2, 3], 0).unwrap());
     }
 
     #[test]
     fn cant_pour_into_full() {
-        let mut tube = Tube::new([0, 0, 1, 2], 0);
-        let mut into = Tube::new([1, 2, 3, 4], 0);
+        let mut tube = Tube::new([0, 0, 1, 2], 0).unwrap();
+        let mut into = Tube::new([1, 2, 3, 4], 0).unwrap();
         assert!(!tube.pour(&mut into));
-        assert_eq!(tube, Tube::new([0, 0, 1, 2], 0));
-        assert_eq!(into, Tube::new([1, 2, 3, 4], 0));
+        assert_eq!(tube, Tube::new([0, 0, 1, 2], 0).unwrap());
+        assert_eq!(into, Tube::new([1, 2, 3, 4], 0).unwrap());
     }
 
     #[test]
     fn cant_pour_wrong_color() {
-        let mut tube = Tube::new([1, 2, 3, 4], 0);
-        let mut into = Tube::new([0, 0, 2, 3], 0);
+        let mut tube = Tube::new([1, 2, 3, 4], 0).unwrap();
+        let mut into = Tube::new([0, 0, 2, 3], 0).unwrap();
         assert!(!tube.pour(&mut into));
-        assert_eq!(tube, Tube::new([1, 2, 3, 4], 0));
-        assert_eq!(into, Tube::new([0, 0, 2, 3], 0));
+        assert_eq!(tube, Tube::new([1, 2, 3, 4], 0).unwrap());
+        assert_eq!(into, Tube::new([0, 0, 2, 3], 0).unwrap());
     }
 
     #[test]
     fn cant_pour_nothing() {
-        let mut tube = Tube::new([0, 0, 0, 0], 0);
-        let mut into = Tube::new([0, 0, 0, 0], 0);
+        let mut tube = Tube::new([0, 0, 0, 0], 0).unwrap();
+        let mut into = Tube::new([0, 0, 0, 0], 0).unwrap();
+        assert!(!tube.pour(&mut into));
+        into = Tube::new([0, 0, 1, 2], 0).unwrap();
         assert!(!tube.pour(&mut into));
-        into = Tube::new([0, 0, 1, 2], 0);
+    }
+
+    #[test]
+    fn can_pour_short_tubes() {
+        let mut tube = Tube::new([1, 1, 2], 0).unwrap();
+        let mut into = Tube::new([0, 0, 1], 0).unwrap();
+        assert!(tube.pour(&mut into));
+        assert_eq!(tube, Tube::new([0, 0, 2], 0).unwrap());
+        assert_eq!(into, Tube::new([1, 1, 1], 0).unwrap());
         assert!(!tube.pour(&mut into));
     }
 
+    #[test]
+    fn can_pour_tall_tubes() {
+        let mut tube = Tube::new([1, 1, 1, 2, 3, 4], 0).unwrap();
+        let mut into = Tube::new([0, 0, 0, 0, 1, 2], 0).unwrap();
+        assert!(tube.pour(&mut into));
+        assert_eq!(tube, Tube::new([0, 0, 0, 2, 3, 4], 0).unwrap());
+        assert_eq!(into, Tube::new([0, 1, 1, 1, 1, 2], 0).unwrap());
+    }
+
+    #[test]
+    fn tube_settle() {
+        let mut tube = Tube::new([1, 0, 0, 0], 0).unwrap();
+        tube.settle();
+        assert_eq!(tube, Tube::new([0, 0, 0, 1], 0).unwrap());
+        let mut tube = Tube::new([4, 0, 3, 0, 5, 0], 0).unwrap();
+        tube.settle();
+        assert_eq!(tube, Tube::new([0, 0, 0, 4, 3, 5], 0).unwrap());
+        let mut tube = Tube::new([0, 1, 2, 3], 0).unwrap();
+        tube.settle();
+        assert_eq!(tube, Tube::new([0, 1, 2, 3], 0).unwrap());
+    }
+
     #[test]
     fn tube_index() {
         test_many_tubes(|colors| {
-            let tube = Tube::new(colors, 0);
+            let tube = Tube::new(colors, 0).unwrap();
             assert_eq!(tube[0], Color::new(colors[0]));
             assert_eq!(tube[1], Color::new(colors[1]));
             assert_eq!(tube[2], Color::new(colors[2]));
@@ -490,10 +1322,305 @@ mod tests {
             game,
             Game {
                 size: 2,
-                state: vec![Tube::new([1, 2, 3, 4], 1), Tube::new([0, 0, 0, 0], 2)],
-                moves: vec![]
+                state: vec![
+                    Tube::new([1, 2, 3, 4], 1).unwrap(),
+                    Tube::new([0, 0, 0, 0], 2).unwrap()
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn game_capacity() {
+        assert_eq!(Game::new(vec![[1, 2, 3, 4], [0; 4]]).capacity(), Some(4));
+        assert_eq!(Game::new(vec![[1, 1, 1], [0; 3]]).capacity(), Some(3));
+        assert_eq!(Game::new(vec![[1; 6], [0; 6]]).capacity(), Some(6));
+        assert_eq!(
+            Game::mixed(vec![vec![1; 4], vec![0; 2]])
+                .unwrap()
+                .capacity(),
+            None
+        );
+    }
+
+    #[test]
+    fn shuffled() {
+        let game = Game::shuffled(5, 4, 2, 7).unwrap();
+        assert_eq!(game, Game::shuffled(5, 4, 2, 7).unwrap());
+        assert_ne!(game, Game::shuffled(5, 4, 2, 8).unwrap());
+        assert_eq!(game.state.len(), 7);
+        assert_eq!(game.capacity(), Some(4));
+        assert_eq!(game.validate(), Ok(()));
+        assert!(game.state[5..].iter().all(|tube| tube.room() == 4));
+    }
+
+    #[test]
+    fn mixed_constructor() {
+        let game = Game::mixed(vec![vec![1, 2, 3, 4], vec![0; 6], vec![0; 2]]).unwrap();
+        assert_eq!(
+            game,
+            Game {
+                size: 3,
+                state: vec![
+                    Tube::new([1, 2, 3, 4], 1).unwrap(),
+                    Tube::new([0; 6], 2).unwrap(),
+                    Tube::new([0; 2], 3).unwrap()
+                ],
             }
         );
+        assert_ne!(Tube::new([0; 2], 0).unwrap(), Tube::new([0; 4], 0).unwrap());
+    }
+
+    #[test]
+    fn can_pour_between_heights() {
+        let mut tall = Tube::new([1, 1, 1, 1, 2, 2], 0).unwrap();
+        let mut short = Tube::new([0, 0], 0).unwrap();
+        assert!(tall.pour(&mut short));
+        assert_eq!(tall, Tube::new([0, 0, 1, 1, 2, 2], 0).unwrap());
+        assert_eq!(short, Tube::new([1, 1], 0).unwrap());
+        assert!(short.pour(&mut tall));
+        assert_eq!(tall, Tube::new([1, 1, 1, 1, 2, 2], 0).unwrap());
+        assert_eq!(short, Tube::new([0, 0], 0).unwrap());
+    }
+
+    #[test]
+    fn mixed_is_solved() {
+        assert!(Game::mixed(vec![vec![1; 6], vec![2; 4], vec![0; 2]])
+            .unwrap()
+            .is_solved());
+        assert!(Game::mixed(vec![vec![0; 6], vec![2; 4], vec![1; 2]])
+            .unwrap()
+            .is_solved());
+        assert!(
+            !Game::mixed(vec![vec![0, 0, 1, 1, 1, 1], vec![2; 4], vec![0; 2]])
+                .unwrap()
+                .is_solved()
+        );
+        assert!(!Game::mixed(vec![vec![1; 6], vec![0, 0, 2, 2], vec![2; 2]])
+            .unwrap()
+            .is_solved());
+    }
+
+    #[test]
+    fn valid_games() {
+        assert_eq!(
+            Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]).validate(),
+            Ok(())
+        );
+        assert_eq!(
+            Game::new(vec![[1, 0, 0, 0], [1, 1, 1, 0]]).validate(),
+            Ok(())
+        );
+        assert_eq!(
+            Game::mixed(vec![vec![1, 2, 1, 2, 1, 1], vec![2, 1, 2, 1], vec![0; 4]])
+                .unwrap()
+                .validate(),
+            Ok(())
+        );
+        assert_eq!(
+            Game::mixed(vec![vec![1, 2, 1, 2], vec![2, 1, 1, 2], vec![0; 2]])
+                .unwrap()
+                .validate(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn no_tubes() {
+        assert_eq!(
+            Game::new::<4>(vec![]).validate(),
+            Err(ValidationError::NoTubes)
+        );
+    }
+
+    #[test]
+    fn too_few_of_a_color() {
+        let game = Game::new(vec![[1, 1, 1, 1], [0, 2, 2, 2], [0; 4]]);
+        assert_eq!(
+            game.problems(),
+            vec![
+                ValidationError::TooFew {
+                    color: Color::Full(2),
+                    count: 3,
+                    expected: 4,
+                    tubes: vec![2]
+                },
+                ValidationError::EmptySlots {
+                    found: 5,
+                    expected: 4,
+                    tubes: vec![2, 3]
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn too_many_of_a_color() {
+        let game = Game::new(vec![[1, 1, 1, 1], [1, 2, 2, 2], [0; 4]]);
+        assert_eq!(
+            game.validate(),
+            Err(ValidationError::TooMany {
+                color: Color::Full(1),
+                count: 5,
+                expected: 4,
+                tubes: vec![1, 2]
+            })
+        );
+    }
+
+    #[test]
+    fn wrong_empty_slots() {
+        // One empty slot too many
+        let game = Game::new(vec![[1, 1, 1, 0], [2, 2, 2, 2], [0; 4]]);
+        assert_eq!(
+            game.problems().last(),
+            Some(&ValidationError::EmptySlots {
+                found: 5,
+                expected: 4,
+                tubes: vec![1, 3]
+            })
+        );
+        // And one too few
+        let game = Game::new(vec![[1, 1, 1, 1], [2, 2, 2, 2], [0, 0, 0, 2]]);
+        assert_eq!(
+            game.problems().last(),
+            Some(&ValidationError::EmptySlots {
+                found: 3,
+                expected: 4,
+                tubes: vec![3]
+            })
+        );
+        // Four empty slots fill a tube, whatever the colors got wrong
+        let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 3], [0; 4]]);
+        assert_eq!(
+            game.problems(),
+            vec![
+                ValidationError::TooFew {
+                    color: Color::Full(1),
+                    count: 3,
+                    expected: 4,
+                    tubes: vec![1, 2]
+                },
+                ValidationError::TooFew {
+                    color: Color::Full(3),
+                    count: 1,
+                    expected: 4,
+                    tubes: vec![2]
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn mixed_color_without_tube() {
+        let game = Game::mixed(vec![vec![1, 1, 2, 3], vec![1, 1, 2, 3], vec![0; 2]]).unwrap();
+        assert_eq!(
+            game.validate(),
+            Err(ValidationError::NotEnoughTubes {
+                capacity: 2,
+                colors: vec![Color::Full(2), Color::Full(3)],
+                tubes: vec![3]
+            })
+        );
+    }
+
+    #[test]
+    fn colors_split_across_tubes() {
+        // Four units of 1 fill both short tubes, so the tall one stays empty
+        let game = Game::mixed(vec![vec![1, 2, 2, 2], vec![2, 1], vec![1, 1], vec![0; 4]]).unwrap();
+        assert_eq!(game.validate(), Ok(()));
+        assert!(matches!(
+            Solver::new(game).unwrap().solve(),
+            SolveOutcome::Solved { .. }
+        ));
+    }
+
+    #[test]
+    fn colors_without_arrangement() {
+        // Each color only fits two tubes of 2, and there are three of those
+        let game = Game::mixed(vec![
+            vec![1, 1, 2],
+            vec![2, 2, 3],
+            vec![3, 3],
+            vec![1, 1],
+            vec![2, 3],
+        ])
+        .unwrap();
+        assert_eq!(
+            game.validate(),
+            Err(ValidationError::NoArrangement {
+                colors: vec![Color::Full(1), Color::Full(2), Color::Full(3)]
+            })
+        );
+    }
+
+    #[test]
+    fn bad_capacities() {
+        assert_eq!(
+            Game::mixed(vec![vec![1, 1], vec![]]),
+            Err(ValidationError::BadCapacity {
+                tube: 2,
+                capacity: 0
+            })
+        );
+        assert_eq!(
+            Game::mixed(vec![vec![0; MAX_CAPACITY + 1]]),
+            Err(ValidationError::BadCapacity {
+                tube: 1,
+                capacity: MAX_CAPACITY + 1
+            })
+        );
+        assert!(Game::shuffled(2, 0, 1, 0).is_err());
+    }
+
+    #[test]
+    fn unknown_tube() {
+        let game = Game::new(vec![[1, 1], [0, 0]]);
+        assert_eq!(game.tube(2), Some(1));
+        assert_eq!(game.tube(3), None);
+    }
+
+    #[test]
+    fn floating_liquid() {
+        let game = Game::new(vec![[0, 0, 1, 1], [2, 1, 0, 0], [1, 2, 2, 2]]);
+        assert_eq!(
+            game.check_settled(),
+            Err(ValidationError::FloatingLiquid {
+                color: Color::Full(1),
+                tube: 2
+            })
+        );
+        assert_eq!(Game::new(vec![[0, 0, 1, 1]]).check_settled(), Ok(()));
+    }
+
+    #[test]
+    fn normalize_strict() {
+        let mut game = Game::new(vec![[1, 0, 0, 0], [1, 1, 1, 0]]);
+        assert_eq!(
+            game.normalize(Gravity::Strict),
+            Err(ValidationError::FloatingLiquid {
+                color: Color::Full(1),
+                tube: 1
+            })
+        );
+        let mut game = Game::new(vec![[0, 0, 0, 1], [0, 1, 1, 1]]);
+        assert_eq!(game.normalize(Gravity::Strict), Ok(()));
+    }
+
+    #[test]
+    fn normalize_settle() {
+        let mut game = Game::new(vec![[2, 0, 0, 0], [1, 1, 1, 0], [2, 2, 2, 1]]);
+        assert_eq!(game.normalize(Gravity::Settle), Ok(()));
+        assert_eq!(game.check_settled(), Ok(()));
+        assert_eq!(
+            game.state,
+            vec![
+                Tube::new([0, 0, 0, 2], 1).unwrap(),
+                Tube::new([0, 1, 1, 1], 2).unwrap(),
+                Tube::new([2, 2, 2, 1], 3).unwrap()
+            ]
+        );
+        assert_eq!(game.tube(3).unwrap(), 2);
     }
 
     #[test]
@@ -502,16 +1629,24 @@ mod tests {
         assert!(!game.pour(0, 0));
     }
 
+    /// Every move, without pruning.
+    fn all_moves(game: &Game) -> Vec<((usize, usize), Game)> {
+        game.moves(&Pruning::none(), &mut Pruned::default())
+    }
+
     #[test]
     fn basic_moves() {
         let game = Game::new(vec![[1, 2, 3, 4], [0; 4]]);
-        let moves = game.moves();
-        assert_eq!(moves, vec![Game::new(vec![[0, 0, 0, 1], [0, 2, 3, 4]])]);
+        let moves = all_moves(&game);
+        assert_eq!(
+            moves,
+            vec![((1, 2), Game::new(vec![[0, 0, 0, 1], [0, 2, 3, 4]]))]
+        );
     }
     #[test]
     fn simple_moves() {
         let game = Game::new(vec![[1, 1, 1, 3], [1, 2, 3, 4], [0; 4]]);
-        let moves = game.moves();
+        let moves: Vec<Game> = all_moves(&game).into_iter().map(|(_, game)| game).collect();
         println!("{:?}", moves);
         assert!(moves.contains(&Game::new(vec![[0, 0, 0, 3], [0, 1, 1, 1], [1, 2, 3, 4]])));
         assert!(moves.contains(&Game::new(vec![[0, 0, 0, 1], [0, 2, 3, 4], [1, 1, 1, 3]])));
@@ -519,10 +1654,580 @@ mod tests {
     #[test]
     fn duplicate_moves() {
         let game = Game::new(vec![[1, 1, 3, 4], [0; 4], [0; 4]]);
-        let moves = game.moves();
+        let moves = all_moves(&game);
         assert_eq!(
             moves,
-            vec![Game::new(vec![[0; 4], [0, 0, 1, 1], [0, 0, 3, 4]])]
+            vec![((1, 2), Game::new(vec![[0; 4], [0, 0, 1, 1], [0, 0, 3, 4]]))]
+        );
+    }
+
+    #[test]
+    fn canonicalize() {
+        let mut game = Game::new(vec![[3, 7, 3, 7], [7, 3, 7, 3], [0; 4]]);
+        let mut renamed = Game::new(vec![[0; 4], [1, 2, 1, 2], [2, 1, 2, 1]]);
+        game.canonicalize();
+        renamed.canonicalize();
+        assert_eq!(game, renamed);
+        assert!(game
+            .state
+            .iter()
+            .flat_map(Tube::colors)
+            .all(|color| matches!(color, Color::Empty | Color::Full(1) | Color::Full(2))));
+    }
+
+    #[test]
+    fn canonicalize_keeps_ids() {
+        let mut game = Game::new(vec![[5, 5, 9, 9], [9, 9, 5, 5], [0; 4]]);
+        let original = game.clone();
+        game.canonicalize();
+        for tube in &game.state {
+            let before = original.state[original.tube(tube.id()).unwrap()];
+            assert_eq!(tube.capacity(), before.capacity());
+            for (color, before) in tube.colors().iter().zip(before.colors()) {
+                assert_eq!(color.is_empty(), before.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn add_empty_tubes() {
+        let mut game = Game::mixed(vec![vec![1, 2, 1, 2], vec![2, 1, 2, 1], vec![0; 2]]).unwrap();
+        game.add_empty_tubes(2, 4).unwrap();
+        assert_eq!(game.size, 5);
+        assert_eq!(game.state[3].id(), 4);
+        assert_eq!(game.state[4].colors(), [Color::Empty; 4]);
+        assert_eq!(game.validate(), Ok(()));
+    }
+
+    #[test]
+    fn extra_tubes() {
+        let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1]]);
+        let mut solver = Solver::new(game.clone()).unwrap();
+        let extra = solver.extra_tubes(3);
+        assert_eq!(extra.tubes, 1);
+        assert_eq!(extra.game.state.len(), 3);
+        let solutions = extra.outcome.into_solutions().unwrap();
+        for solution in solutions {
+            let mut game = extra.game.clone();
+            for (a, b) in solution {
+                let (i, o) = (game.tube(a).unwrap(), game.tube(b).unwrap());
+                assert!(game.pour(i, o));
+            }
+            assert!(game.is_solved());
+        }
+        // The solver still solves the game it was made with
+        assert!(matches!(solver.solve(), SolveOutcome::Unsolvable { .. }));
+
+        let extra = Solver::new(game).unwrap().extra_tubes(0);
+        assert_eq!(extra.tubes, 0);
+        assert!(matches!(extra.outcome, SolveOutcome::Unsolvable { .. }));
+    }
+
+    #[test]
+    fn canonicalize_ignores_tube_order() {
+        let mut game = Game::new(vec![
+            [0, 1, 1, 4],
+            [0, 2, 2, 2],
+            [0, 3, 3, 4],
+            [0, 3, 2, 1],
+            [4, 1, 4, 3],
+        ]);
+        let mut reversed = game.clone();
+        reversed.state.reverse();
+        game.canonicalize();
+        reversed.canonicalize();
+        assert_eq!(game, reversed);
+    }
+
+    #[test]
+    fn moves_stay_sorted() {
+        let mut game = Game::new(vec![[1, 2, 1, 2], [0, 0, 2, 1], [0; 4], [0, 1, 2, 1]]);
+        game.normalize(Gravity::Settle).unwrap();
+        let unsorted = all_moves(&game);
+        game.sort();
+        let sorted = all_moves(&game);
+        assert_eq!(unsorted.len(), sorted.len());
+        for (_, child) in &unsorted {
+            assert!(sorted.iter().any(|(_, other)| other == child));
+        }
+        for (_, child) in sorted {
+            assert!(child.state.is_sorted());
+        }
+    }
+
+    // Checks the children of the first layers of level 3145 with an extra
+    // empty tube against building every child in full and comparing it with
+    // the others, as moves used to.
+    #[test]
+    fn moves_dedup_game_3145_extra() {
+        let naive_moves = |game: &Game| {
+            let mut games: Vec<Game> = vec![];
+            for a in 0..game.size {
+                for b in 0..game.size {
+                    let Some((tx, rx)) = game.try_pour(a, b) else {
+                        continue;
+                    };
+                    let mut child = game.clone();
+                    child.state[a] = tx;
+                    child.state[b] = rx;
+                    child.sort();
+                    if !games.contains(&child) {
+                        games.push(child);
+                    }
+                }
+            }
+            games
+        };
+        let mut game = Puzzle::parse(include_str!("../puzzles/game_3145_extra.txt"))
+            .unwrap()
+            .game;
+        game.sort();
+        assert_eq!(all_moves(&game).len(), 12);
+        let mut seen = HashSet::from([game.clone()]);
+        let mut layer = vec![game];
+        let mut children = 0;
+        for _ in 0..3 {
+            let mut next = vec![];
+            for game in &layer {
+                let moves: Vec<Game> = all_moves(game).into_iter().map(|(_, game)| game).collect();
+                let naive = naive_moves(game);
+                assert_eq!(moves.len(), naive.len());
+                assert!(naive.iter().all(|child| moves.contains(child)));
+                children += moves.len();
+                for child in moves {
+                    if seen.insert(child.clone()) {
+                        next.push(child);
+                    }
+                }
+            }
+            layer = next;
+        }
+        assert_eq!(children, 1595);
+        assert_eq!(seen.len(), 638);
+    }
+
+    fn strategies() -> Vec<Strategy> {
+        vec![
+            Strategy::Bfs,
+            Strategy::AStar(Box::new(ExtraBlocks)),
+            Strategy::IdaStar(Box::new(ExtraBlocks)),
+            Strategy::Dfs(Box::new(ExtraBlocks)),
+            Strategy::Bidirectional,
+        ]
+    }
+
+    #[test]
+    fn solver_depth_limit() {
+        for strategy in strategies() {
+            let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]);
+            let limits = Limits {
+                max_depth: Some(6),
+                ..Limits::default()
+            };
+            let mut solver = Solver::new(game)
+                .unwrap()
+                .with_strategy(strategy)
+                .with_limits(limits);
+            assert!(matches!(
+                solver.solve(),
+                SolveOutcome::Aborted {
+                    limit: Limit::Depth,
+                    ..
+                }
+            ));
+        }
+        let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]);
+        let limits = Limits {
+            max_depth: Some(7),
+            ..Limits::default()
+        };
+        let mut solver = Solver::new(game).unwrap().with_limits(limits);
+        assert!(matches!(
+            solver.solve(),
+            SolveOutcome::Solved { depth: 7, .. }
+        ));
+    }
+
+    #[test]
+    fn solver_state_limit() {
+        for strategy in strategies() {
+            let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]);
+            let limits = Limits {
+                max_states: Some(2),
+                ..Limits::default()
+            };
+            let mut solver = Solver::new(game)
+                .unwrap()
+                .with_strategy(strategy)
+                .with_limits(limits);
+            let outcome = solver.solve();
+            assert!(matches!(
+                outcome,
+                SolveOutcome::Aborted {
+                    limit: Limit::States,
+                    ..
+                }
+            ));
+            assert_eq!(outcome.stats().expanded, 2);
+            assert!(solver.get_solutions().is_none());
+        }
+    }
+
+    #[test]
+    fn solves_again_after_abort() {
+        let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]);
+        let limits = Limits {
+            max_states: Some(2),
+            ..Limits::default()
+        };
+        let mut solver = Solver::new(game.clone()).unwrap().with_limits(limits);
+        assert!(matches!(solver.solve(), SolveOutcome::Aborted { .. }));
+        let mut solver = solver.with_limits(Limits::default());
+        assert_eq!(
+            solver.get_solutions(),
+            Solver::new(game).unwrap().get_solutions()
+        );
+    }
+
+    #[test]
+    fn solver_cancelled() {
+        for strategy in strategies() {
+            let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]);
+            let cancel = CancelToken::new();
+            let limits = Limits {
+                cancel: Some(cancel.clone()),
+                ..Limits::default()
+            };
+            let mut solver = Solver::new(game)
+                .unwrap()
+                .with_strategy(strategy)
+                .with_limits(limits);
+            cancel.cancel();
+            assert!(matches!(
+                solver.solve(),
+                SolveOutcome::Aborted {
+                    limit: Limit::Cancelled,
+                    ..
+                }
+            ));
+        }
+    }
+
+    #[test]
+    fn unsolvable_within_limits() {
+        for strategy in strategies() {
+            let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1]]);
+            let limits = Limits {
+                max_states: Some(100),
+                max_depth: Some(100),
+                ..Limits::default()
+            };
+            let mut solver = Solver::new(game)
+                .unwrap()
+                .with_strategy(strategy)
+                .with_limits(limits);
+            assert!(matches!(solver.solve(), SolveOutcome::Unsolvable { .. }));
+        }
+    }
+
+    fn solved_games() -> [Game; 2] {
+        [
+            Game::new(vec![[1; 4], [2; 4], [0; 4]]),
+            Game::mixed(vec![vec![1, 1], vec![1, 1], vec![0; 4]]).unwrap(),
+        ]
+    }
+
+    #[test]
+    fn solved_start() {
+        for game in solved_games() {
+            for strategy in strategies() {
+                let outcome = Solver::new(game.clone())
+                    .unwrap()
+                    .with_strategy(strategy)
+                    .solve();
+                assert!(matches!(outcome, SolveOutcome::Solved { depth: 0, .. }));
+                assert_eq!(outcome.into_solutions(), Some(vec![vec![]]));
+            }
+            let outcome = Solver::new(game.clone()).unwrap().with_threads(4).solve();
+            assert!(matches!(outcome, SolveOutcome::Solved { depth: 0, .. }));
+            assert_eq!(outcome.into_solutions(), Some(vec![vec![]]));
+        }
+    }
+
+    #[test]
+    fn solve_stats() {
+        for strategy in strategies() {
+            let optimal = strategy.is_optimal();
+            let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]);
+            let outcome = Solver::new(game).unwrap().with_strategy(strategy).solve();
+            let stats = *outcome.stats();
+            assert!(stats.expanded > 0);
+            assert!(stats.visited > 0);
+            assert!(stats.peak_frontier > 0);
+            assert!(stats.depth > 0);
+            match outcome {
+                SolveOutcome::Solved {
+                    solutions, depth, ..
+                } => {
+                    assert!(solutions.iter().all(|solution| solution.len() == depth));
+                    assert!(!optimal || depth == 7);
+                }
+                _ => panic!("Expected a solution"),
+            }
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct Recorder {
+        progress: Vec<Progress>,
+        solutions: usize,
+        finished: bool,
+    }
+
+    /// Lets a test look at the recorder after the solver has taken it.
+    #[derive(Debug, Default, Clone)]
+    struct Shared(std::sync::Arc<std::sync::Mutex<Recorder>>);
+
+    impl SolverObserver for Shared {
+        fn on_progress(&mut self, progress: &Progress) {
+            self.0.lock().unwrap().progress.push(*progress);
+        }
+
+        fn on_solution(&mut self, _solution: &Solution) {
+            self.0.lock().unwrap().solutions += 1;
+        }
+
+        fn on_finish(&mut self, _outcome: &SolveOutcome) {
+            self.0.lock().unwrap().finished = true;
+        }
+    }
+
+    #[test]
+    fn observer() {
+        for strategy in strategies() {
+            let bfs = matches!(strategy, Strategy::Bfs);
+            let dfs = matches!(strategy, Strategy::Dfs(_));
+            let ida = matches!(strategy, Strategy::IdaStar(_));
+            let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]);
+            let shared = Shared::default();
+            let outcome = Solver::new(game)
+                .unwrap()
+                .with_strategy(strategy)
+                .with_observer(Box::new(shared.clone()))
+                .solve();
+            let recorder = shared.0.lock().unwrap();
+            assert!(recorder.finished);
+            assert_eq!(recorder.solutions, outcome.into_solutions().unwrap().len());
+            assert!(dfs || !recorder.progress.is_empty());
+            assert!(recorder
+                .progress
+                .windows(2)
+                .all(|pair| pair[0].depth < pair[1].depth));
+            assert!(
+                !ida || recorder
+                    .progress
+                    .iter()
+                    .all(|progress| progress.frontier > 0)
+            );
+            if bfs {
+                assert_eq!(recorder.progress.len(), 7);
+                assert!(recorder
+                    .progress
+                    .iter()
+                    .all(|progress| progress.visited > 0));
+            }
+        }
+    }
+
+    #[test]
+    fn symmetry() {
+        let game = Game::new(vec![
+            [1, 2, 3, 4],
+            [2, 3, 4, 1],
+            [3, 4, 1, 2],
+            [4, 1, 2, 3],
+            [0; 4],
+            [0; 4],
+        ]);
+        for strategy in strategies() {
+            let optimal = strategy.is_optimal();
+            let plain = Solver::new(game.clone()).unwrap().solve();
+            let outcome = Solver::new(game.clone())
+                .unwrap()
+                .with_strategy(strategy)
+                .with_symmetry(true)
+                .solve();
+            let (plain_depth, depth) = match (&plain, &outcome) {
+                (SolveOutcome::Solved { depth: plain, .. }, SolveOutcome::Solved { depth, .. }) => {
+                    (*plain, *depth)
+                }
+                _ => panic!("Expected a solution"),
+            };
+            assert!(depth == plain_depth || !optimal);
+            for solution in outcome.into_solutions().unwrap() {
+                let mut game = game.clone();
+                for (a, b) in solution {
+                    let (i, o) = (game.tube(a).unwrap(), game.tube(b).unwrap());
+                    assert!(game.pour(i, o));
+                }
+                assert!(game.is_solved());
+            }
+        }
+        let plain = Solver::new(game.clone()).unwrap().solve();
+        let reduced = Solver::new(game).unwrap().with_symmetry(true).solve();
+        assert!(reduced.stats().visited < plain.stats().visited);
+    }
+
+    #[test]
+    fn symmetry_keeps_game() {
+        let game = Game::new(vec![
+            [4, 3, 2, 1],
+            [3, 2, 1, 4],
+            [2, 1, 4, 3],
+            [1, 4, 3, 2],
+            [0; 4],
+        ]);
+        let mut renamed = game.clone();
+        renamed.canonicalize();
+        assert!(renamed != game);
+        let solver = Solver::new(game.clone()).unwrap().with_symmetry(true);
+        assert!(solver.game == game);
+        let solver = solver.with_symmetry(false);
+        assert!(solver.game == game);
+        assert!(solver.states.game(solver.queue[0]) == game);
+        let extra = solver.with_symmetry(true).extra_tubes(1);
+        assert!(extra.game.state[..game.state.len()] == game.state[..]);
+    }
+
+    #[test]
+    fn pruning() {
+        let game = Game::new(vec![
+            [1, 2, 3, 4],
+            [2, 3, 4, 1],
+            [3, 4, 1, 2],
+            [4, 1, 2, 3],
+            [0; 4],
+            [0; 4],
+        ]);
+        for strategy in strategies() {
+            let outcome = Solver::new(game.clone())
+                .unwrap()
+                .with_strategy(strategy)
+                .solve();
+            assert!(outcome.stats().pruned.total() > 0);
+        }
+        let plain = Solver::new(game.clone())
+            .unwrap()
+            .with_pruning(Pruning::none())
+            .solve();
+        assert_eq!(plain.stats().pruned, Pruned::default());
+        let pruned = Solver::new(game.clone()).unwrap().solve();
+        assert_eq!(
+            pruned.into_solutions().unwrap()[0].len(),
+            plain.into_solutions().unwrap()[0].len()
         );
+
+        let solver = Solver::new(game).unwrap().with_pruning(Pruning {
+            whole_blocks: true,
+            ..Pruning::default()
+        });
+        assert!(!solver.is_optimal());
+    }
+
+    #[test]
+    fn pruning_keeps_solved_starts() {
+        // Every pour out of a finished tube is cut, so only checking the
+        // start before expanding it finds these solved
+        let rules = [
+            Pruning::default(),
+            Pruning {
+                whole_blocks: true,
+                ..Pruning::default()
+            },
+        ];
+        for game in solved_games() {
+            for pruning in rules {
+                for strategy in strategies() {
+                    let outcome = Solver::new(game.clone())
+                        .unwrap()
+                        .with_strategy(strategy)
+                        .with_pruning(pruning)
+                        .solve();
+                    assert_eq!(outcome.into_solutions(), Some(vec![vec![]]));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn threads() {
+        let games = [
+            Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]),
+            Game::new(vec![
+                [1, 2, 3, 4],
+                [2, 3, 4, 1],
+                [3, 4, 1, 2],
+                [4, 1, 2, 3],
+                [0; 4],
+                [0; 4],
+            ]),
+            Game::mixed(vec![vec![1, 2, 1, 2], vec![2, 1, 2, 1], vec![0; 6]]).unwrap(),
+        ];
+        for game in games {
+            let plain = Solver::new(game.clone()).unwrap().solve();
+            for threads in [2, 3, 8] {
+                let outcome = Solver::new(game.clone())
+                    .unwrap()
+                    .with_threads(threads)
+                    .solve();
+                let (
+                    SolveOutcome::Solved { depth, .. },
+                    SolveOutcome::Solved {
+                        depth: plain_depth, ..
+                    },
+                ) = (&outcome, &plain)
+                else {
+                    panic!("Expected a solution");
+                };
+                assert_eq!(depth, plain_depth);
+                assert_eq!(outcome.stats().visited, plain.stats().visited);
+                let solutions = outcome.into_solutions().unwrap();
+                assert_eq!(
+                    solutions.len(),
+                    plain.clone().into_solutions().unwrap().len()
+                );
+                for solution in solutions {
+                    let mut game = game.clone();
+                    for (a, b) in solution {
+                        let (i, o) = (game.tube(a).unwrap(), game.tube(b).unwrap());
+                        assert!(game.pour(i, o));
+                    }
+                    assert!(game.is_solved());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn threads_find_no_solution() {
+        let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1]]);
+        let outcome = Solver::new(game).unwrap().with_threads(4).solve();
+        assert!(matches!(outcome, SolveOutcome::Unsolvable { .. }));
+        let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]);
+        let outcome = Solver::new(game)
+            .unwrap()
+            .with_threads(4)
+            .with_limits(Limits {
+                max_depth: Some(2),
+                ..Limits::default()
+            })
+            .solve();
+        assert!(matches!(
+            outcome,
+            SolveOutcome::Aborted {
+                limit: Limit::Depth,
+                ..
+            }
+        ));
     }
 }
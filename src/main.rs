@@ -1,65 +1,455 @@
-use tube::{Game, Solver};
-fn main() {
-    let mut game = Game::new(vec![
-        [1, 1, 1, 2],
-        [3, 4, 5, 6],
-        [3, 7, 5, 8],
-        [9, 8, 5, 6],
-        [2, 10, 7, 11],
-        [12, 2, 12, 3],
-        [3, 8, 12, 11],
-        [4, 8, 9, 4],
-        [11, 1, 9, 6],
-        [4, 10, 11, 10],
-        [12, 10, 6, 9],
-        [7, 2, 7, 5],
-        [0; 4],
-        [0; 4],
-    ]);
-    Solver::new(game).solve();
-    // let mut solver = Solver::new(game.clone());
-    // for (from, to) in solver
-    //     .get_solutions()
-    //     .unwrap()
-    //     .iter()
-    //     .nth(0)
-    //     .unwrap()
-    //     .iter()
-    // {
-    //     println!("Game: {:?}", game);
-    //     println!("Move {from} to {to}");
-    //     if !game.pour(from - 1, to - 1) {
-    //         println!("MOVE INVALID");
-    //     }
-    // }
-    // println!("Game: {:?}", game);
+use std::{
+    env, fs,
+    process::ExitCode,
+    str::FromStr,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use tube::{
+    verify_solution, Boundaries, ExtraBlocks, Game, Heuristic, Limits, Palette, Progress, Puzzle,
+    Report, SolveOutcome, Solver, SolverObserver, Strategy, MAX_CAPACITY,
+};
+
+const USAGE: &str = "\
+Usage: tube <command> [options]
+
+Commands:
+  solve [puzzle]              Solve a puzzle file, or level 3149 if none is given
+    --strategy <name>         bfs, astar, ida, dfs or bidirectional (default bfs)
+    --heuristic <name>        extra-blocks or boundaries, for astar, ida and dfs
+                              (default extra-blocks)
+    --max-states <n>          Give up after expanding n games
+    --max-depth <n>           Only look for solutions of up to n moves
+    --timeout <seconds>       Give up after this long
+    --threads <n>             Split a breadth first search across n threads
+    --symmetry                Treat games that only differ by colors as the same
+    --quiet                   Don't print progress
+    --format <name>           text, json or ndjson (default text)
+  verify <puzzle> <solution>  Check that a solution solves a puzzle. The solution
+                              lists the tube ids of each move, like `1 3` per line
+                              or the list `solve` prints
+  render <puzzle>             Print a puzzle file back out neatly
+    --numbers                 Write colors as numbers rather than by name
+  generate                    Print a random puzzle, which may not be solvable
+    --colors <n>              How many colors (default 4)
+    --capacity <n>            How much each tube holds (default 4)
+    --empty <n>               How many empty tubes (default 2)
+    --seed <n>                Pick the puzzle, rather than at random
+    --numbers                 Write colors as numbers rather than by name
+  help                        Print this message
+
+Colors in puzzle files can be numbers or the names of the standard palette.
+
+Exit codes:
+  0  Solved, or the command worked
+  1  Unsolvable, or the solution doesn't solve the puzzle
+  2  Aborted by a limit
+  3  Bad arguments or input";
+
+/// What the process exits with, so that scripts can tell runs apart.
+const SOLVED: u8 = 0;
+const UNSOLVABLE: u8 = 1;
+const ABORTED: u8 = 2;
+const BAD_INPUT: u8 = 3;
+
+#[derive(Debug)]
+struct PrintProgress;
+
+impl SolverObserver for PrintProgress {
+    fn on_progress(&mut self, progress: &Progress) {
+        eprintln!("Games to check: {}", progress.frontier);
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.split_first() {
+        Some((command, args)) => match command.as_str() {
+            "solve" => solve(args),
+            "verify" => verify(args),
+            "render" => render(args),
+            "generate" => generate(args),
+            "help" | "--help" | "-h" => {
+                println!("{USAGE}");
+                Ok(SOLVED)
+            }
+            _ => Err(format!("Unknown command {command:?}")),
+        },
+        None => Err("No command given".to_owned()),
+    };
+    match result {
+        Ok(code) => ExitCode::from(code),
+        Err(error) => {
+            eprintln!("{error}\nRun `tube help` for usage");
+            ExitCode::from(BAD_INPUT)
+        }
+    }
+}
+
+fn solve(args: &[String]) -> Result<u8, String> {
+    let args = Args::parse(
+        args,
+        &[
+            "--strategy",
+            "--heuristic",
+            "--max-states",
+            "--max-depth",
+            "--timeout",
+            "--threads",
+            "--format",
+        ],
+        &["--symmetry", "--quiet"],
+    )?;
+    let palette = Palette::standard();
+    let puzzle = match args.files[..] {
+        [] => Puzzle::parse(include_str!("../puzzles/game_3149.txt")).expect("Level 3149 parses"),
+        [path] => read_puzzle(path, &palette)?,
+        _ => return Err("solve takes one puzzle file".to_owned()),
+    };
+    let format = args.value::<String>("--format")?;
+    if let Some(other) = format
+        .as_deref()
+        .filter(|format| !["text", "json", "ndjson"].contains(format))
+    {
+        return Err(format!("Unknown format {other:?}"));
+    }
+
+    let heuristic = || -> Result<Box<dyn Heuristic>, String> {
+        match args.value::<String>("--heuristic")?.as_deref() {
+            None | Some("extra-blocks") => Ok(Box::new(ExtraBlocks)),
+            Some("boundaries") => Ok(Box::new(Boundaries)),
+            Some(other) => Err(format!("Unknown heuristic {other:?}")),
+        }
+    };
+    let strategy = match args.value::<String>("--strategy")?.as_deref() {
+        None | Some("bfs") => Strategy::Bfs,
+        Some("astar") => Strategy::AStar(heuristic()?),
+        Some("ida") => Strategy::IdaStar(heuristic()?),
+        Some("dfs") => Strategy::Dfs(heuristic()?),
+        Some("bidirectional") => Strategy::Bidirectional,
+        Some(other) => return Err(format!("Unknown strategy {other:?}")),
+    };
+    let timeout = match args.value::<f64>("--timeout")? {
+        Some(seconds) => Some(
+            Duration::try_from_secs_f64(seconds)
+                .map_err(|_| format!("Invalid value \"{seconds}\" for --timeout"))?,
+        ),
+        None => None,
+    };
+    let limits = Limits {
+        max_states: args.value("--max-states")?,
+        max_depth: args.value("--max-depth")?,
+        deadline: timeout.map(|timeout| Instant::now() + timeout),
+        cancel: None,
+    };
+
+    let mut solver = Solver::new(puzzle.game.clone())
+        .map_err(|error| format!("Invalid game: {error}"))?
+        .with_strategy(strategy)
+        .with_limits(limits)
+        .with_symmetry(args.flag("--symmetry"))
+        .with_threads(args.value("--threads")?.unwrap_or(1));
+    if !args.flag("--quiet") {
+        solver = solver.with_observer(Box::new(PrintProgress));
+    }
+    let outcome = solver.solve();
+    let report = Report {
+        puzzle: &puzzle,
+        outcome: &outcome,
+        palette: &palette,
+    };
+    match format.as_deref() {
+        Some("json") => println!("{report}"),
+        Some("ndjson") => print!("{}", report.to_ndjson()),
+        _ => {
+            println!("{outcome}");
+            for solution in outcome.clone().into_solutions().unwrap_or_default() {
+                println!("{solution:?}");
+                for step in palette.describe(&puzzle.game, &solution) {
+                    println!("  {step}");
+                }
+            }
+        }
+    }
+    Ok(match outcome {
+        SolveOutcome::Solved { .. } => SOLVED,
+        SolveOutcome::Unsolvable { .. } => UNSOLVABLE,
+        SolveOutcome::Aborted { .. } => ABORTED,
+    })
+}
+
+fn verify(args: &[String]) -> Result<u8, String> {
+    let args = Args::parse(args, &[], &[])?;
+    let [puzzle, solution] = args.files[..] else {
+        return Err("verify takes a puzzle file and a solution file".to_owned());
+    };
+    let game = read_puzzle(puzzle, &Palette::standard())?.game;
+    let text = fs::read_to_string(solution)
+        .map_err(|error| format!("Unable to read {solution}: {error}"))?;
+    let moves = parse_moves(&text).map_err(|error| format!("{solution}: {error}"))?;
+
+    let replay = verify_solution(&game, &moves);
+    if let Some(error) = replay.error {
+        println!("{error}");
+        return Ok(UNSOLVABLE);
+    }
+    if replay.solved {
+        println!("Solved in {} moves", moves.len());
+        Ok(SOLVED)
+    } else {
+        println!("Not solved after {} moves", moves.len());
+        Ok(UNSOLVABLE)
+    }
+}
+
+fn render(args: &[String]) -> Result<u8, String> {
+    let args = Args::parse(args, &[], &["--numbers"])?;
+    let [path] = args.files[..] else {
+        return Err("render takes one puzzle file".to_owned());
+    };
+    let palette = Palette::standard();
+    let puzzle = read_puzzle(path, &palette)?;
+    if args.flag("--numbers") {
+        print!("{puzzle}");
+    } else {
+        print!("{}", puzzle.render(&palette));
+    }
+    Ok(SOLVED)
+}
+
+fn generate(args: &[String]) -> Result<u8, String> {
+    let args = Args::parse(
+        args,
+        &["--colors", "--capacity", "--empty", "--seed"],
+        &["--numbers"],
+    )?;
+    if let Some(path) = args.files.first() {
+        return Err(format!("generate takes no files, got {path:?}"));
+    }
+    let colors: u8 = args.value("--colors")?.unwrap_or(4);
+    let capacity: usize = args.value("--capacity")?.unwrap_or(4);
+    let empty: usize = args.value("--empty")?.unwrap_or(2);
+    if colors == 0 {
+        return Err("--colors has to be at least 1".to_owned());
+    }
+    if !(1..=MAX_CAPACITY).contains(&capacity) {
+        return Err(format!("--capacity has to be from 1 to {MAX_CAPACITY}"));
+    }
+    let seed = match args.value("--seed")? {
+        Some(seed) => seed,
+        None => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64),
+    };
+
+    let puzzle = Puzzle {
+        game: Game::shuffled(colors, capacity, empty, seed).map_err(|error| error.to_string())?,
+        level: None,
+        capacity: Some(capacity),
+    };
+    println!("# Seed {seed}");
+    if args.flag("--numbers") {
+        print!("{puzzle}");
+    } else {
+        print!("{}", puzzle.render(&Palette::standard()));
+    }
+    Ok(SOLVED)
+}
+
+fn read_puzzle(path: &str, palette: &Palette) -> Result<Puzzle, String> {
+    let text =
+        fs::read_to_string(path).map_err(|error| format!("Unable to read {path}: {error}"))?;
+    Puzzle::parse_with(&text, palette).map_err(|error| format!("{path}: {error}"))
+}
+
+/// Reads the moves of a solution as pairs of tube ids, poured from and into.
+/// Only the numbers count, so `1 3` on each line and the list `solve` prints,
+/// like `[(1, 3), (2, 1)]`, both work. Anything after a `#` is a comment.
+fn parse_moves(text: &str) -> Result<Vec<(usize, usize)>, String> {
+    let mut ids = vec![];
+    for line in text.lines() {
+        let content = line.split('#').next().unwrap_or_default();
+        for word in content
+            .split(|c: char| !c.is_ascii_digit())
+            .filter(|word| !word.is_empty())
+        {
+            ids.push(
+                word.parse()
+                    .map_err(|_| format!("{word} is not a tube id"))?,
+            );
+        }
+    }
+    if ids.len() % 2 != 0 {
+        return Err(format!("{} tube ids don't make whole moves", ids.len()));
+    }
+    Ok(ids.chunks(2).map(|ids| (ids[0], ids[1])).collect())
+}
+
+/// The arguments after a command, split into the files it names and its
+/// options.
+struct Args<'a> {
+    files: Vec<&'a str>,
+    /// Each option given, with its value if it takes one.
+    options: Vec<(&'a str, Option<&'a str>)>,
+}
+
+impl<'a> Args<'a> {
+    /// Splits `args`, where `valued` are the options followed by a value and
+    /// `flags` are the ones on their own.
+    fn parse(args: &'a [String], valued: &[&str], flags: &[&str]) -> Result<Self, String> {
+        let mut parsed = Args {
+            files: vec![],
+            options: vec![],
+        };
+        let mut args = args.iter().map(String::as_str);
+        while let Some(arg) = args.next() {
+            if valued.contains(&arg) {
+                let value = args.next().ok_or(format!("{arg} needs a value"))?;
+                parsed.options.push((arg, Some(value)));
+            } else if flags.contains(&arg) {
+                parsed.options.push((arg, None));
+            } else if arg.starts_with("--") {
+                return Err(format!("Unknown option {arg}"));
+            } else {
+                parsed.files.push(arg);
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.options.iter().any(|&(option, _)| option == name)
+    }
+
+    /// The last value given for option `name`, if any.
+    fn value<T: FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+        let Some(value) = self
+            .options
+            .iter()
+            .rev()
+            .find_map(|&(option, value)| (option == name).then_some(value).flatten())
+        else {
+            return Ok(None);
+        };
+        value
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("Invalid value {value:?} for {name}"))
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use tube::{Game, Solver};
+    use super::{parse_moves, solve, verify_solution, SOLVED};
+    use std::{
+        env, fs, process,
+        time::{Duration, Instant},
+    };
+    use tube::{
+        Boundaries, Disk, ExtraBlocks, Game, Gravity, Heuristic, Limit, Limits, Puzzle,
+        SolveOutcome, Solver, Strategy,
+    };
     // --- Solutions ---
 
-    fn test_game(mut game: Game, fails: bool) {
-        let solutions = Solver::new(game.clone()).get_solutions();
+    fn test_game(game: Game, fails: bool) {
+        let solutions = Solver::new(game.clone()).unwrap().get_solutions();
         if fails {
             assert!(solutions.is_none());
+            let meeting = Solver::new(game)
+                .unwrap()
+                .with_strategy(Strategy::Bidirectional)
+                .get_solutions();
+            assert!(meeting.is_none());
             return;
         } else {
             assert!(solutions.is_some());
         }
-        let solution = solutions.as_ref().unwrap().first().unwrap();
-        for (a, b) in solution {
-            let i = game.tube(*a);
-            let o = game.tube(*b);
-            game.pour(i, o);
+        replay(game, solutions.unwrap().first().unwrap());
+    }
+
+    /// Checks the other strategies against the breadth first search. They
+    /// take too long unoptimized to run on every game, so this only runs on
+    /// a few of each kind.
+    fn test_strategies(game: Game) {
+        let shortest = Solver::new(game.clone()).unwrap().get_solutions().unwrap()[0].len();
+        for strategy in [
+            Strategy::Bidirectional,
+            Strategy::IdaStar(Box::new(ExtraBlocks)),
+        ] {
+            let solutions = Solver::new(game.clone())
+                .unwrap()
+                .with_strategy(strategy)
+                .get_solutions()
+                .unwrap();
+            assert_eq!(solutions[0].len(), shortest);
+            replay(game.clone(), &solutions[0]);
         }
-        assert!(game.is_solved());
+
+        let mut solver = Solver::new(game.clone())
+            .unwrap()
+            .with_strategy(Strategy::Dfs(Box::new(ExtraBlocks)));
+        assert!(!solver.is_optimal());
+        let any = solver.get_solutions().unwrap();
+        assert!(any[0].len() >= shortest);
+        replay(game, &any[0]);
+    }
+
+    fn test_astar(game: Game, heuristic: Box<dyn Heuristic>) {
+        let shortest = Solver::new(game.clone())
+            .unwrap()
+            .get_solutions()
+            .map(|solutions| solutions[0].len());
+        let solutions = Solver::new(game.clone())
+            .unwrap()
+            .with_strategy(Strategy::AStar(heuristic))
+            .get_solutions();
+        assert_eq!(
+            solutions.as_ref().map(|solutions| solutions[0].len()),
+            shortest
+        );
+        if let Some(solutions) = solutions {
+            replay(game, &solutions[0]);
+        }
+    }
+
+    fn test_symmetry(game: Game) {
+        let plain = Solver::new(game.clone()).unwrap().solve();
+        let reduced = Solver::new(game.clone())
+            .unwrap()
+            .with_symmetry(true)
+            .solve();
+        assert!(reduced.stats().visited <= plain.stats().visited);
+        let plain = plain.into_solutions().unwrap();
+        let reduced = reduced.into_solutions().unwrap();
+        assert_eq!(reduced[0].len(), plain[0].len());
+        for solution in reduced {
+            replay(game.clone(), &solution);
+        }
+    }
+
+    fn test_threads(game: Game) {
+        let plain = Solver::new(game.clone()).unwrap().solve();
+        let parallel = Solver::new(game.clone()).unwrap().with_threads(4).solve();
+        assert_eq!(parallel.stats().visited, plain.stats().visited);
+        let plain = plain.into_solutions().unwrap();
+        let parallel = parallel.into_solutions().unwrap();
+        assert_eq!(parallel[0].len(), plain[0].len());
+        assert_eq!(parallel.len(), plain.len());
+        for solution in parallel {
+            replay(game.clone(), &solution);
+        }
+    }
+
+    fn replay(game: Game, solution: &[(usize, usize)]) {
+        let replay = verify_solution(&game, solution);
+        assert_eq!(replay.error, None);
+        assert!(replay.solved);
     }
 
     #[test]
     fn simple_game() {
-        let mut solver = Solver::new(Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0, 0, 0, 0]]));
+        let mut solver =
+            Solver::new(Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0, 0, 0, 0]])).unwrap();
         assert!(solver.get_solutions().unwrap().contains(&vec![
             (1, 3),
             (2, 1),
@@ -75,6 +465,16 @@ mod test {
         test_game(Game::new(vec![[1, 0, 0, 0], [1, 1, 1, 0]]), false);
     }
     #[test]
+    fn game_1_strict() {
+        let mut game = Game::new(vec![[1, 0, 0, 0], [1, 1, 1, 0]]);
+        assert!(game.normalize(Gravity::Strict).is_err());
+    }
+    #[test]
+    fn game_1_settled() {
+        let mut solver = Solver::new(Game::new(vec![[1, 0, 0, 0], [1, 1, 1, 0]])).unwrap();
+        assert_eq!(solver.get_solutions(), Some(vec![vec![(1, 2)]]));
+    }
+    #[test]
     fn game_2() {
         test_game(Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]), false);
     }
@@ -1007,6 +1407,74 @@ mod test {
         );
     }
     #[test]
+    fn game_3_slots() {
+        test_game(
+            Game::new(vec![[1, 2, 3], [3, 1, 2], [2, 3, 1], [0; 3]]),
+            false,
+        );
+    }
+    #[test]
+    fn game_5_slots() {
+        test_game(
+            Game::new(vec![
+                [1, 2, 1, 2, 3],
+                [3, 1, 2, 3, 1],
+                [2, 3, 1, 3, 2],
+                [0; 5],
+                [0; 5],
+            ]),
+            false,
+        );
+    }
+    #[test]
+    fn game_6_slots() {
+        test_game(
+            Game::new(vec![[1, 2, 1, 2, 1, 2], [2, 1, 2, 1, 2, 1], [0; 6]]),
+            false,
+        );
+    }
+    #[test]
+    fn game_bonus_tube() {
+        test_game(
+            Game::mixed(vec![
+                vec![1, 2, 3, 4],
+                vec![4, 3, 2, 1],
+                vec![2, 4, 1, 3],
+                vec![3, 1, 4, 2],
+                vec![0; 6],
+            ])
+            .unwrap(),
+            false,
+        );
+    }
+    #[test]
+    fn game_helper_tube() {
+        test_game(
+            Game::mixed(vec![
+                vec![1, 2, 1, 2],
+                vec![2, 3, 1, 3],
+                vec![3, 2, 3, 1],
+                vec![0; 4],
+                vec![0; 2],
+            ])
+            .unwrap(),
+            false,
+        );
+    }
+    #[test]
+    fn game_tall_color() {
+        test_game(
+            Game::mixed(vec![
+                vec![1, 2, 1, 2, 1, 1],
+                vec![2, 1, 2, 1],
+                vec![0; 4],
+                vec![0; 6],
+            ])
+            .unwrap(),
+            false,
+        );
+    }
+    #[test]
     fn game_1964() {
         test_game(
             Game::new(vec![
@@ -1089,7 +1557,423 @@ mod test {
                 [0; 4],
                 [0; 4],
             ]),
-            true,
+            false,
+        );
+    }
+    #[test]
+    fn strategies_game_3() {
+        test_strategies(Game::new(vec![
+            [1, 2, 3, 1],
+            [1, 2, 3, 3],
+            [2, 3, 1, 2],
+            [0; 4],
+            [0; 4],
+        ]));
+    }
+    #[test]
+    fn strategies_game_31() {
+        test_strategies(Game::new(vec![
+            [1, 2, 3, 4],
+            [4, 5, 1, 6],
+            [7, 3, 8, 4],
+            [6, 7, 7, 2],
+            [2, 9, 9, 8],
+            [3, 5, 7, 4],
+            [6, 1, 9, 3],
+            [5, 9, 5, 8],
+            [1, 8, 6, 2],
+            [0; 4],
+            [0; 4],
+        ]));
+    }
+    #[test]
+    fn strategies_game_61() {
+        test_strategies(Game::new(vec![
+            [1, 2, 2, 3],
+            [4, 5, 1, 6],
+            [1, 2, 7, 3],
+            [4, 2, 8, 5],
+            [9, 3, 6, 6],
+            [5, 9, 9, 4],
+            [3, 1, 8, 8],
+            [7, 4, 9, 8],
+            [7, 5, 7, 6],
+            [0; 4],
+            [0; 4],
+        ]));
+    }
+    #[test]
+    fn strategies_game_5_slots() {
+        test_strategies(Game::new(vec![
+            [1, 2, 1, 2, 3],
+            [3, 1, 2, 3, 1],
+            [2, 3, 1, 3, 2],
+            [0; 5],
+            [0; 5],
+        ]));
+    }
+    #[test]
+    fn strategies_game_bonus_tube() {
+        test_strategies(
+            Game::mixed(vec![
+                vec![1, 2, 3, 4],
+                vec![4, 3, 2, 1],
+                vec![2, 4, 1, 3],
+                vec![3, 1, 4, 2],
+                vec![0; 6],
+            ])
+            .unwrap(),
+        );
+    }
+    #[test]
+    fn strategies_game_3149() {
+        test_strategies(Game::new(vec![
+            [1, 1, 1, 2],
+            [3, 4, 5, 6],
+            [3, 7, 5, 8],
+            [9, 8, 5, 6],
+            [2, 10, 7, 11],
+            [12, 2, 12, 3],
+            [3, 8, 12, 11],
+            [4, 8, 9, 4],
+            [11, 1, 9, 6],
+            [4, 10, 11, 10],
+            [12, 10, 6, 9],
+            [7, 2, 7, 5],
+            [0; 4],
+            [0; 4],
+        ]));
+    }
+    // The larger games are slow unoptimized, run them with
+    // `cargo test --release -- --ignored strategies`
+    #[test]
+    #[ignore]
+    fn strategies_game_1964() {
+        test_strategies(Game::new(vec![
+            [8, 8, 7, 3],
+            [4, 3, 5, 0],
+            [4, 5, 0, 4],
+            [4, 7, 2, 0],
+            [3, 6, 1, 2],
+            [2, 8, 5, 1],
+            [7, 6, 1, 2],
+            [3, 6, 1, 6],
+            [5, 7, 8, 0],
+            [0; 4],
+            [0; 4],
+        ]));
+    }
+    #[test]
+    #[ignore]
+    fn strategies_game_3145_extra() {
+        test_strategies(Game::new(vec![
+            [4, 3, 2, 1],
+            [1, 7, 6, 5],
+            [9, 8, 2, 5],
+            [9, 8, 2, 9],
+            [11, 10, 3, 5],
+            [10, 3, 7, 4],
+            [11, 5, 6, 12],
+            [1, 6, 11, 4],
+            [4, 10, 8, 6],
+            [3, 10, 7, 9],
+            [12, 7, 8, 12],
+            [12, 1, 2, 11],
+            [0; 4],
+            [0; 4],
+            [0; 4],
+        ]));
+    }
+    #[test]
+    fn astar_game_3() {
+        test_astar(
+            Game::new(vec![
+                [1, 2, 3, 1],
+                [1, 2, 3, 3],
+                [2, 3, 1, 2],
+                [0; 4],
+                [0; 4],
+            ]),
+            Box::new(Boundaries),
+        );
+    }
+    #[test]
+    fn astar_game_31() {
+        test_astar(
+            Game::new(vec![
+                [1, 2, 3, 4],
+                [4, 5, 1, 6],
+                [7, 3, 8, 4],
+                [6, 7, 7, 2],
+                [2, 9, 9, 8],
+                [3, 5, 7, 4],
+                [6, 1, 9, 3],
+                [5, 9, 5, 8],
+                [1, 8, 6, 2],
+                [0; 4],
+                [0; 4],
+            ]),
+            Box::new(ExtraBlocks),
+        );
+    }
+    #[test]
+    fn astar_game_1964() {
+        test_astar(
+            Game::new(vec![
+                [8, 8, 7, 3],
+                [4, 3, 5, 0],
+                [4, 5, 0, 4],
+                [4, 7, 2, 0],
+                [3, 6, 1, 2],
+                [2, 8, 5, 1],
+                [7, 6, 1, 2],
+                [3, 6, 1, 6],
+                [5, 7, 8, 0],
+                [0; 4],
+                [0; 4],
+            ]),
+            Box::new(ExtraBlocks),
         );
     }
+    #[test]
+    fn astar_game_3149() {
+        test_astar(
+            Game::new(vec![
+                [1, 1, 1, 2],
+                [3, 4, 5, 6],
+                [3, 7, 5, 8],
+                [9, 8, 5, 6],
+                [2, 10, 7, 11],
+                [12, 2, 12, 3],
+                [3, 8, 12, 11],
+                [4, 8, 9, 4],
+                [11, 1, 9, 6],
+                [4, 10, 11, 10],
+                [12, 10, 6, 9],
+                [7, 2, 7, 5],
+                [0; 4],
+                [0; 4],
+            ]),
+            Box::new(ExtraBlocks),
+        );
+    }
+    #[test]
+    fn game_3145_extra_deadline() {
+        let game = Game::new(vec![
+            [4, 3, 2, 1],
+            [1, 7, 6, 5],
+            [9, 8, 2, 5],
+            [9, 8, 2, 9],
+            [11, 10, 3, 5],
+            [10, 3, 7, 4],
+            [11, 5, 6, 12],
+            [1, 6, 11, 4],
+            [4, 10, 8, 6],
+            [3, 10, 7, 9],
+            [12, 7, 8, 12],
+            [12, 1, 2, 11],
+            [0; 4],
+            [0; 4],
+            [0; 4],
+        ]);
+        let limits = Limits {
+            deadline: Some(Instant::now() + Duration::from_millis(50)),
+            ..Limits::default()
+        };
+        let outcome = Solver::new(game).unwrap().with_limits(limits).solve();
+        assert!(matches!(
+            outcome,
+            SolveOutcome::Aborted {
+                limit: Limit::Deadline,
+                ..
+            }
+        ));
+        assert!(outcome.stats().expanded > 0);
+    }
+    #[test]
+    fn symmetry_game_3() {
+        test_symmetry(Game::new(vec![
+            [1, 2, 3, 1],
+            [1, 2, 3, 3],
+            [2, 3, 1, 2],
+            [0; 4],
+            [0; 4],
+        ]));
+    }
+    #[test]
+    fn symmetry_game_4() {
+        test_symmetry(Game::new(vec![
+            [1, 1, 2, 3],
+            [2, 3, 2, 3],
+            [1, 2, 3, 1],
+            [0; 4],
+            [0; 4],
+        ]));
+    }
+    #[test]
+    fn threads_game_3() {
+        test_threads(Game::new(vec![
+            [1, 2, 3, 1],
+            [1, 2, 3, 3],
+            [2, 3, 1, 2],
+            [0; 4],
+            [0; 4],
+        ]));
+    }
+    #[test]
+    fn game_3145_on_disk() {
+        let game = Game::new(vec![
+            [4, 3, 2, 1],
+            [1, 7, 6, 5],
+            [9, 8, 2, 5],
+            [9, 8, 2, 9],
+            [11, 10, 3, 5],
+            [10, 3, 7, 4],
+            [11, 5, 6, 12],
+            [1, 6, 11, 4],
+            [4, 10, 8, 6],
+            [3, 10, 7, 9],
+            [12, 7, 8, 12],
+            [12, 1, 2, 11],
+            [0; 4],
+            [0; 4],
+        ]);
+        let disk = Disk {
+            games_in_memory: 100,
+            ..Disk::default()
+        };
+        let plain = Solver::new(game.clone()).unwrap().solve();
+        let outcome = Solver::new(game).unwrap().with_disk(disk).solve();
+        assert!(matches!(outcome, SolveOutcome::Unsolvable { .. }));
+        assert_eq!(outcome.stats().visited, plain.stats().visited);
+    }
+    #[test]
+    fn game_3145_certificate() {
+        let game = Game::new(vec![
+            [4, 3, 2, 1],
+            [1, 7, 6, 5],
+            [9, 8, 2, 5],
+            [9, 8, 2, 9],
+            [11, 10, 3, 5],
+            [10, 3, 7, 4],
+            [11, 5, 6, 12],
+            [1, 6, 11, 4],
+            [4, 10, 8, 6],
+            [3, 10, 7, 9],
+            [12, 7, 8, 12],
+            [12, 1, 2, 11],
+            [0; 4],
+            [0; 4],
+        ]);
+        let certificate = Solver::new(game.clone()).unwrap().certify().unwrap();
+        assert_eq!(certificate.verify(&game), Ok(()));
+    }
+    #[test]
+    fn game_3149_has_no_certificate() {
+        let game = Game::new(vec![
+            [1, 1, 1, 2],
+            [3, 4, 5, 6],
+            [3, 7, 5, 8],
+            [9, 8, 5, 6],
+            [2, 10, 7, 11],
+            [12, 2, 12, 3],
+            [3, 8, 12, 11],
+            [4, 8, 9, 4],
+            [11, 1, 9, 6],
+            [4, 10, 11, 10],
+            [12, 10, 6, 9],
+            [7, 2, 7, 5],
+            [0; 4],
+            [0; 4],
+        ]);
+        let outcome = Solver::new(game.clone()).unwrap().certify().unwrap_err();
+        let solutions = outcome.into_solutions().unwrap();
+        replay(game, &solutions[0]);
+    }
+    #[test]
+    fn game_3145_extra_tubes() {
+        let game = Game::new(vec![
+            [4, 3, 2, 1],
+            [1, 7, 6, 5],
+            [9, 8, 2, 5],
+            [9, 8, 2, 9],
+            [11, 10, 3, 5],
+            [10, 3, 7, 4],
+            [11, 5, 6, 12],
+            [1, 6, 11, 4],
+            [4, 10, 8, 6],
+            [3, 10, 7, 9],
+            [12, 7, 8, 12],
+            [12, 1, 2, 11],
+            [0; 4],
+            [0; 4],
+        ]);
+        let extra = Solver::new(game)
+            .unwrap()
+            .with_strategy(Strategy::AStar(Box::new(ExtraBlocks)))
+            .extra_tubes(2);
+        assert_eq!(extra.tubes, 1);
+        let SolveOutcome::Solved {
+            depth, solutions, ..
+        } = extra.outcome
+        else {
+            panic!("Expected a solution");
+        };
+        assert_eq!(depth, 41);
+        replay(extra.game, &solutions[0]);
+    }
+    #[test]
+    fn solution_files() {
+        let moves = vec![(1, 3), (2, 1), (12, 10)];
+        assert_eq!(
+            parse_moves("1 3\n2 1 # Comment 4\n\n12 10"),
+            Ok(moves.clone())
+        );
+        assert_eq!(parse_moves(&format!("{moves:?}")), Ok(moves));
+        assert!(parse_moves("1 3\n2").is_err());
+    }
+
+    #[test]
+    fn solve_solved_file() {
+        let path = env::temp_dir().join(format!("tube-solved-{}.txt", process::id()));
+        fs::write(&path, "1 1 1 1\n2 2 2 2\n. . . .\n").unwrap();
+        for options in [&[][..], &["--threads", "4"], &["--format", "json"]] {
+            let mut args = vec![path.to_str().unwrap(), "--quiet"];
+            args.extend(options);
+            let args: Vec<String> = args.into_iter().map(str::to_owned).collect();
+            assert_eq!(solve(&args), Ok(SOLVED), "{options:?}");
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn puzzle_files() {
+        let puzzle = Puzzle::parse(include_str!("../puzzles/game_3145.txt")).unwrap();
+        assert_eq!(puzzle.level, Some(3145));
+        assert_eq!(
+            puzzle.game,
+            Game::new(vec![
+                [4, 3, 2, 1],
+                [1, 7, 6, 5],
+                [9, 8, 2, 5],
+                [9, 8, 2, 9],
+                [11, 10, 3, 5],
+                [10, 3, 7, 4],
+                [11, 5, 6, 12],
+                [1, 6, 11, 4],
+                [4, 10, 8, 6],
+                [3, 10, 7, 9],
+                [12, 7, 8, 12],
+                [12, 1, 2, 11],
+                [0; 4],
+                [0; 4],
+            ])
+        );
+        for text in [
+            include_str!("../puzzles/game_3145_extra.txt"),
+            include_str!("../puzzles/game_3149.txt"),
+        ] {
+            assert_eq!(Puzzle::parse(text).unwrap().game.validate(), Ok(()));
+        }
+    }
 }
@@ -0,0 +1,39 @@
+use std::{fmt, time::Duration};
+
+use crate::{Solution, SolveOutcome};
+
+/// A snapshot of a running solve.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// The number of moves searched so far. For A* and IDA* this is the bound
+    /// on solution length currently being searched.
+    pub depth: usize,
+    /// Games waiting to be expanded. IDA* keeps no such games, only the
+    /// path to the game it is expanding, so for it this is the longest that
+    /// path got during the iteration.
+    pub frontier: usize,
+    /// Games the search remembers having seen.
+    pub visited: usize,
+    /// Games whose moves have been generated.
+    pub expanded: usize,
+    pub elapsed: Duration,
+}
+
+/// Hears about a solve while it runs. Every method does nothing by default.
+/// To stop a solve from an observer, cancel the [`CancelToken`] given in its
+/// [`Limits`].
+///
+/// [`CancelToken`]: crate::CancelToken
+/// [`Limits`]: crate::Limits
+pub trait SolverObserver: fmt::Debug + Send {
+    /// Called after each BFS layer or search iteration. Depth-first search
+    /// has neither, so it calls this every few thousand games instead. See
+    /// [`Progress::frontier`] for what the frontier is with IDA*.
+    fn on_progress(&mut self, _progress: &Progress) {}
+
+    /// Called for every solution as it is found.
+    fn on_solution(&mut self, _solution: &Solution) {}
+
+    /// Called once the solve has ended.
+    fn on_finish(&mut self, _outcome: &SolveOutcome) {}
+}
@@ -0,0 +1,342 @@
+use std::{
+    cmp::Ordering,
+    hash::{Hash, Hasher},
+    io::{self, Read, Write},
+};
+
+use crate::{Color, Game, Tube, MAX_CAPACITY};
+
+/// Bits used to store the capacity of a tube, less one.
+const CAPACITY_BITS: u32 = (MAX_CAPACITY - 1).ilog2() + 1;
+
+/// A game packed into a few machine words, for remembering many of them.
+///
+/// Each tube takes a few bits for its capacity and as few bits per slot as
+/// the highest color needs, so the colors of a game of fourteen tubes
+/// holding four of twelve colors fit in five words. The tube ids follow in
+/// their own words. Like [`Game`], packed games compare and hash by their
+/// colors only.
+#[derive(Clone, Debug)]
+pub struct PackedGame {
+    tubes: u32,
+    color_bits: u8,
+    id_bits: u8,
+    /// How many of the words hold capacities and colors rather than ids.
+    key_words: u32,
+    words: Box<[u64]>,
+}
+
+impl PackedGame {
+    pub fn unpack(&self) -> Game {
+        let mut colors = Reader::new(self.key());
+        let mut ids = Reader::new(&self.words[self.key_words as usize..]);
+        let state = (0..self.tubes)
+            .map(|_| {
+                let capacity = colors.read(CAPACITY_BITS) as usize + 1;
+                let mut tube = Tube {
+                    colors: [Color::Empty; MAX_CAPACITY],
+                    capacity,
+                    id: 0,
+                };
+                for color in &mut tube.colors[..capacity] {
+                    *color = match colors.read(self.color_bits.into()) {
+                        0 => Color::Empty,
+                        code => Color::Full((code - 1) as u8),
+                    };
+                }
+                tube.id = ids.read(self.id_bits.into()) as usize;
+                tube
+            })
+            .collect();
+        Game {
+            size: self.tubes as usize,
+            state,
+        }
+    }
+
+    fn key(&self) -> &[u64] {
+        &self.words[..self.key_words as usize]
+    }
+
+    /// Writes the game out, to be read back by
+    /// [`read_from`](PackedGame::read_from).
+    pub(crate) fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(&self.tubes.to_le_bytes())?;
+        out.write_all(&[self.color_bits, self.id_bits])?;
+        out.write_all(&self.key_words.to_le_bytes())?;
+        out.write_all(&(self.words.len() as u32).to_le_bytes())?;
+        for word in &self.words {
+            out.write_all(&word.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Reads the next game written by [`write_to`](PackedGame::write_to), or
+    /// `None` at the end of the input.
+    pub(crate) fn read_from(input: &mut impl Read) -> io::Result<Option<Self>> {
+        let mut tubes = [0; 4];
+        match input.read_exact(&mut tubes) {
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            result => result?,
+        }
+        let mut bits = [0; 2];
+        input.read_exact(&mut bits)?;
+        let mut key_words = [0; 4];
+        input.read_exact(&mut key_words)?;
+        let mut len = [0; 4];
+        input.read_exact(&mut len)?;
+        let words = (0..u32::from_le_bytes(len))
+            .map(|_| {
+                let mut word = [0; 8];
+                input.read_exact(&mut word)?;
+                Ok(u64::from_le_bytes(word))
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(Some(PackedGame {
+            tubes: u32::from_le_bytes(tubes),
+            color_bits: bits[0],
+            id_bits: bits[1],
+            key_words: u32::from_le_bytes(key_words),
+            words,
+        }))
+    }
+}
+
+impl Game {
+    /// Packs the game for compact storage. See [`PackedGame`].
+    pub fn pack(&self) -> PackedGame {
+        let code = |color: &Color| match color {
+            Color::Empty => 0,
+            // One higher, so that `Full(0)` doesn't read back as empty
+            Color::Full(number) => u64::from(*number) + 1,
+        };
+        let highest = self
+            .state
+            .iter()
+            .flat_map(Tube::colors)
+            .map(code)
+            .max()
+            .unwrap_or(0);
+        let most_id = self.state.iter().map(Tube::id).max().unwrap_or(0);
+        let color_bits = bits(highest);
+        let id_bits = bits(most_id as u64);
+
+        let mut key = Writer::default();
+        for tube in &self.state {
+            key.write(tube.capacity as u64 - 1, CAPACITY_BITS);
+            for color in tube.colors() {
+                key.write(code(color), color_bits);
+            }
+        }
+        let mut ids = Writer::default();
+        for tube in &self.state {
+            ids.write(tube.id as u64, id_bits);
+        }
+        let key_words = key.words.len() as u32;
+        let mut words = key.words;
+        words.extend(ids.words);
+        PackedGame {
+            tubes: self.state.len() as u32,
+            color_bits: color_bits as u8,
+            id_bits: id_bits as u8,
+            key_words,
+            words: words.into_boxed_slice(),
+        }
+    }
+}
+
+impl From<&Game> for PackedGame {
+    fn from(game: &Game) -> Self {
+        game.pack()
+    }
+}
+
+impl From<&PackedGame> for Game {
+    fn from(packed: &PackedGame) -> Self {
+        packed.unpack()
+    }
+}
+
+impl PartialEq for PackedGame {
+    fn eq(&self, other: &Self) -> bool {
+        self.tubes == other.tubes
+            && self.color_bits == other.color_bits
+            && self.key() == other.key()
+    }
+}
+impl Eq for PackedGame {}
+
+/// Orders games by their colors in no meaningful way, but consistently with
+/// equality, so that equal games sort next to each other.
+impl Ord for PackedGame {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.tubes, self.color_bits, self.key()).cmp(&(other.tubes, other.color_bits, other.key()))
+    }
+}
+
+impl PartialOrd for PackedGame {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Hash for PackedGame {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.tubes.hash(state);
+        self.key().hash(state);
+    }
+}
+
+/// The bits needed to write `value`, at least one.
+fn bits(value: u64) -> u32 {
+    value.checked_ilog2().map_or(1, |log| log + 1)
+}
+
+/// Writes values of a few bits each into words, never splitting a value
+/// across two words.
+#[derive(Default)]
+struct Writer {
+    words: Vec<u64>,
+    used: u32,
+}
+
+impl Writer {
+    fn write(&mut self, value: u64, bits: u32) {
+        if self.words.is_empty() || self.used + bits > u64::BITS {
+            self.words.push(0);
+            self.used = 0;
+        }
+        *self.words.last_mut().expect("Word was pushed") |= value << self.used;
+        self.used += bits;
+    }
+}
+
+/// Reads values back in the order a [`Writer`] wrote them.
+struct Reader<'a> {
+    words: &'a [u64],
+    index: usize,
+    used: u32,
+}
+
+impl<'a> Reader<'a> {
+    fn new(words: &'a [u64]) -> Self {
+        Reader {
+            words,
+            index: 0,
+            used: 0,
+        }
+    }
+
+    fn read(&mut self, bits: u32) -> u64 {
+        if self.used + bits > u64::BITS {
+            self.index += 1;
+            self.used = 0;
+        }
+        let value = (self.words[self.index] >> self.used) & (u64::MAX >> (u64::BITS - bits));
+        self.used += bits;
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big_game() -> Game {
+        Game::new(vec![
+            [1, 2, 3, 4],
+            [5, 6, 7, 1],
+            [2, 3, 4, 5],
+            [6, 7, 1, 2],
+            [3, 4, 5, 6],
+            [7, 1, 2, 3],
+            [4, 5, 6, 7],
+            [8, 9, 10, 11],
+            [12, 8, 9, 10],
+            [11, 12, 8, 9],
+            [10, 11, 12, 8],
+            [9, 10, 11, 12],
+            [0; 4],
+            [0; 4],
+        ])
+    }
+
+    #[test]
+    fn round_trip() {
+        let game = big_game();
+        let unpacked = game.pack().unpack();
+        assert_eq!(unpacked, game);
+        for (tube, unpacked) in game.state.iter().zip(&unpacked.state) {
+            assert_eq!(tube.id(), unpacked.id());
+            assert_eq!(tube.capacity(), unpacked.capacity());
+        }
+
+        let mixed = Game::mixed(vec![vec![1, 2, 1], vec![2, 1, 2, 1, 2], vec![0; 8]]).unwrap();
+        assert_eq!(mixed.pack().unpack(), mixed);
+        assert_eq!(Game::from(&PackedGame::from(&mixed)), mixed);
+    }
+
+    #[test]
+    fn keeps_every_color() {
+        let mut game = Game::new(vec![[0, 255, 1, 1]]);
+        game.state[0].colors[3] = Color::Full(0);
+        let unpacked = game.pack().unpack();
+        assert_eq!(unpacked.state[0].colors(), game.state[0].colors());
+    }
+
+    #[test]
+    fn ignores_ids() {
+        let game = big_game();
+        let mut reordered = game.clone();
+        reordered.state.swap(12, 13);
+        assert_ne!(reordered.state[12].id(), game.state[12].id());
+        assert_eq!(reordered.pack(), game.pack());
+
+        let mut moved = game.clone();
+        assert!(moved.pour(0, 12));
+        assert_ne!(moved.pack(), game.pack());
+    }
+
+    #[test]
+    fn write_and_read() {
+        let games = [
+            big_game(),
+            Game::mixed(vec![vec![1, 2, 1], vec![2, 1, 2, 1, 2], vec![0; 8]]).unwrap(),
+        ];
+        let mut bytes = vec![];
+        for game in &games {
+            game.pack().write_to(&mut bytes).unwrap();
+        }
+        let mut input = &bytes[..];
+        for game in &games {
+            let read = PackedGame::read_from(&mut input).unwrap().unwrap();
+            assert_eq!(read, game.pack());
+            assert_eq!(read.words, game.pack().words);
+        }
+        assert!(PackedGame::read_from(&mut input).unwrap().is_none());
+        assert!(PackedGame::read_from(&mut &bytes[..7]).is_err());
+    }
+
+    #[test]
+    fn sorts_equal_games_together() {
+        let game = big_game();
+        let mut moved = game.clone();
+        assert!(moved.pour(0, 12));
+        let mut reordered = game.clone();
+        reordered.state.swap(12, 13);
+        let mut games = [game.pack(), moved.pack(), reordered.pack()];
+        games.sort();
+        assert!(games[0] == games[1] || games[1] == games[2]);
+        assert_eq!(game.pack().cmp(&reordered.pack()), Ordering::Equal);
+    }
+
+    #[test]
+    fn smaller_than_game() {
+        let game = big_game();
+        let packed = game.pack();
+        assert_eq!(packed.key_words, 5);
+        let packed_size = size_of::<PackedGame>() + size_of_val(&*packed.words);
+        let game_size = size_of::<Game>() + size_of_val(&*game.state);
+        assert!(packed_size * 4 < game_size, "{packed_size} vs {game_size}");
+    }
+}
@@ -0,0 +1,166 @@
+use crate::{Color, Game, Pour, Solution};
+
+/// Names for the colors of a game, so that puzzles can be typed in and
+/// solutions read with the names the game shows rather than numbers.
+///
+/// Each color number can have several names, such as a short code and a
+/// full name. The first one is used when writing the color out, and any of
+/// them is read back, whatever its case.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Palette {
+    /// Every name with its color number, in the order they were given.
+    names: Vec<(String, u8)>,
+}
+
+impl Palette {
+    /// A palette with no names, where colors are only numbers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Twelve common colors, each with a one letter code that is written out
+    /// and a full name:
+    ///
+    /// | Number | Code | Name            |
+    /// |--------|------|-----------------|
+    /// | 1      | `R`  | red             |
+    /// | 2      | `G`  | green           |
+    /// | 3      | `B`  | blue            |
+    /// | 4      | `Y`  | yellow          |
+    /// | 5      | `O`  | orange          |
+    /// | 6      | `P`  | purple          |
+    /// | 7      | `K`  | pink            |
+    /// | 8      | `L`  | lime            |
+    /// | 9      | `C`  | cyan            |
+    /// | 10     | `N`  | brown           |
+    /// | 11     | `A`  | gray, grey      |
+    /// | 12     | `S`  | sky             |
+    pub fn standard() -> Self {
+        Palette::new()
+            .with(1, ["R", "red"])
+            .with(2, ["G", "green"])
+            .with(3, ["B", "blue"])
+            .with(4, ["Y", "yellow"])
+            .with(5, ["O", "orange"])
+            .with(6, ["P", "purple"])
+            .with(7, ["K", "pink"])
+            .with(8, ["L", "lime"])
+            .with(9, ["C", "cyan"])
+            .with(10, ["N", "brown"])
+            .with(11, ["A", "gray", "grey"])
+            .with(12, ["S", "sky"])
+    }
+
+    /// Adds `names` for color `number`, the first of them used to write it.
+    ///
+    /// Panics if the number is 0, which means empty, or if a name is taken,
+    /// is a number, or couldn't be read back from a puzzle file.
+    pub fn with<'a>(mut self, number: u8, names: impl IntoIterator<Item = &'a str>) -> Self {
+        assert_ne!(number, 0, "Color 0 is empty and can't be named");
+        for name in names {
+            assert!(
+                !name.is_empty()
+                    && name != "."
+                    && name.parse::<u64>().is_err()
+                    && !name.contains(|c: char| c.is_whitespace() || c == '#' || c == ':'),
+                "{name:?} can't be used as a color name"
+            );
+            assert!(self.color(name).is_none(), "{name:?} is already taken");
+            self.names.push((name.to_owned(), number));
+        }
+        self
+    }
+
+    /// The color called `name`, ignoring case.
+    pub fn color(&self, name: &str) -> Option<Color> {
+        self.number(name).map(Color::Full)
+    }
+
+    /// The number of the color called `name`, ignoring case.
+    pub(crate) fn number(&self, name: &str) -> Option<u8> {
+        self.names
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(name))
+            .map(|&(_, number)| number)
+    }
+
+    /// The name `color` is written with, if it has one.
+    pub fn name(&self, color: Color) -> Option<&str> {
+        self.names
+            .iter()
+            .find(|&&(_, number)| color == Color::Full(number))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// How `color` is written: its name, or its number if it has none, or
+    /// `.` if it is empty.
+    pub fn write(&self, color: Color) -> String {
+        match (color, self.name(color)) {
+            (Color::Empty, _) => ".".to_owned(),
+            (_, Some(name)) => name.to_owned(),
+            (Color::Full(number), None) => number.to_string(),
+        }
+    }
+
+    /// Describes each move of `solution` as it is made on `game`, naming the
+    /// color poured, such as "Pour lime from tube 3 into tube 5". Stops at the
+    /// first move that can't be made. The game is settled first, as the
+    /// solver does.
+    pub fn describe(&self, game: &Game, solution: &Solution) -> Vec<String> {
+        Pour::replay(game, solution)
+            .iter()
+            .map(|pour| {
+                format!(
+                    "Pour {} from tube {} into tube {}",
+                    self.write(pour.color),
+                    pour.from,
+                    pour.to
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        let palette = Palette::standard();
+        assert_eq!(palette.color("lime"), Some(Color::Full(8)));
+        assert_eq!(palette.color("LIME"), Some(Color::Full(8)));
+        assert_eq!(palette.color("grey"), palette.color("gray"));
+        assert_eq!(palette.color("mauve"), None);
+        assert_eq!(palette.name(Color::Full(8)), Some("L"));
+        assert_eq!(palette.write(Color::Full(8)), "L");
+        assert_eq!(palette.write(Color::Full(40)), "40");
+        assert_eq!(palette.write(Color::Empty), ".");
+    }
+
+    #[test]
+    #[should_panic(expected = "already taken")]
+    fn names_are_unique() {
+        Palette::new().with(1, ["red"]).with(2, ["Red"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "can't be used")]
+    fn names_are_not_numbers() {
+        Palette::new().with(1, ["12"]);
+    }
+
+    #[test]
+    fn describes_moves() {
+        let palette = Palette::new().with(1, ["pink"]).with(2, ["lime"]);
+        let game = Game::new(vec![[0, 0, 1, 2], [0, 0, 2, 1], [0, 0, 1, 2]]);
+        // The last pour puts pink on lime, so it stops there
+        assert_eq!(
+            palette.describe(&game, &vec![(1, 3), (2, 1), (2, 1)]),
+            [
+                "Pour pink from tube 1 into tube 3",
+                "Pour lime from tube 2 into tube 1",
+            ]
+        );
+    }
+}
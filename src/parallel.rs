@@ -0,0 +1,177 @@
+use std::{collections::hash_map::RandomState, hash::BuildHasher, thread};
+
+use crate::{search::Expansion, Budget, Game, Limit, PackedGame, Pruned, Solution, Store};
+
+/// A breadth first search that splits every layer over `threads` threads.
+///
+/// The games seen are spread over one store per thread by their hash, so
+/// that each store is only written by one thread at a time. Each layer is
+/// expanded in two steps: the threads first make the children of their part
+/// of the layer, reading the stores to drop games seen in earlier layers,
+/// then each thread stores the children that belong in its store. Finds the
+/// same shortest solutions as the solver's own breadth first search.
+///
+/// Limits are checked for a whole layer before it is expanded, so a search
+/// can only stop between layers.
+pub(crate) fn search(
+    game: &Game,
+    threads: usize,
+    budget: &mut Budget,
+) -> Result<Option<Vec<Solution>>, Limit> {
+    if game.is_solved() {
+        let solution = vec![];
+        budget.found(&solution);
+        return Ok(Some(vec![solution]));
+    }
+    let threads = threads.max(1);
+    let hasher = RandomState::new();
+    let mut shards: Vec<Store> = (0..threads)
+        .map(|_| Store::with_hasher(hasher.clone()))
+        .collect();
+    let start = game.pack();
+    let hash = hasher.hash_one(&start);
+    let shard = shard(hash, threads);
+    let index = shards[shard]
+        .insert_packed(hash, start, None, 0)
+        .expect("The stores start empty");
+    let mut layer = vec![index * threads + shard];
+    let expansion = budget.expansion;
+
+    for depth in 0.. {
+        if budget.too_deep(depth + 1) {
+            return Err(Limit::Depth);
+        }
+        for _ in &layer {
+            budget.expand(depth)?;
+        }
+
+        let chunk = layer.len().div_ceil(threads);
+        let parts: Vec<Expanded> = thread::scope(|scope| {
+            let workers: Vec<_> = layer
+                .chunks(chunk)
+                .map(|ids| scope.spawn(|| expand(&shards, ids, expansion, &hasher)))
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("Expanding thread panicked"))
+                .collect()
+        });
+
+        let mut solutions = vec![];
+        let mut incoming: Vec<Vec<Child>> = (0..threads).map(|_| vec![]).collect();
+        for part in parts {
+            budget.pruned(&part.pruned);
+            for (id, step) in part.solutions {
+                let mut solution = path(&shards, id);
+                solution.push(step);
+                budget.found(&solution);
+                solutions.push(solution);
+            }
+            for (children, bucket) in incoming.iter_mut().zip(part.buckets) {
+                children.extend(bucket);
+            }
+        }
+
+        let next: Vec<Vec<usize>> = thread::scope(|scope| {
+            let workers: Vec<_> = shards
+                .iter_mut()
+                .zip(incoming)
+                .enumerate()
+                .map(|(shard, (store, children))| {
+                    scope.spawn(move || {
+                        children
+                            .into_iter()
+                            .filter_map(|child| {
+                                store.insert_packed(
+                                    child.hash,
+                                    child.game,
+                                    Some((child.parent, child.step)),
+                                    depth + 1,
+                                )
+                            })
+                            .map(|index| index * threads + shard)
+                            .collect()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("Storing thread panicked"))
+                .collect()
+        });
+        layer = next.concat();
+
+        let visited = shards.iter().map(Store::len).sum();
+        budget.progress(depth + 1, layer.len(), visited);
+        if !solutions.is_empty() {
+            return Ok(Some(solutions));
+        }
+        if layer.is_empty() {
+            return Ok(None);
+        }
+    }
+    unreachable!("Depths never run out")
+}
+
+/// A new game found by a thread, waiting to be stored.
+struct Child {
+    parent: usize,
+    step: (usize, usize),
+    hash: u64,
+    game: PackedGame,
+}
+
+/// What one thread made of its part of a layer.
+struct Expanded {
+    /// The new games, by the store they belong in.
+    buckets: Vec<Vec<Child>>,
+    /// The games that had a solved child, and the move that solves them.
+    solutions: Vec<(usize, (usize, usize))>,
+    pruned: Pruned,
+}
+
+fn expand(shards: &[Store], ids: &[usize], expansion: Expansion, hasher: &RandomState) -> Expanded {
+    let mut expanded = Expanded {
+        buckets: (0..shards.len()).map(|_| vec![]).collect(),
+        solutions: vec![],
+        pruned: Pruned::default(),
+    };
+    for &id in ids {
+        let game = shards[id % shards.len()].game(id / shards.len());
+        for (step, child) in expansion.children(&game, &mut expanded.pruned) {
+            if child.is_solved() {
+                expanded.solutions.push((id, step));
+                continue;
+            }
+            let game = child.pack();
+            let hash = hasher.hash_one(&game);
+            let shard = shard(hash, shards.len());
+            if shards[shard].find_hashed(hash, &game).is_none() {
+                expanded.buckets[shard].push(Child {
+                    parent: id,
+                    step,
+                    hash,
+                    game,
+                });
+            }
+        }
+    }
+    expanded
+}
+
+/// The store a game with `hash` goes in.
+fn shard(hash: u64, shards: usize) -> usize {
+    (hash % shards as u64) as usize
+}
+
+/// The moves from the start to the game with `id`, following parents across
+/// the stores.
+fn path(shards: &[Store], mut id: usize) -> Solution {
+    let mut moves = vec![];
+    while let Some((parent, step)) = shards[id % shards.len()].parent(id / shards.len()) {
+        moves.push(step);
+        id = parent;
+    }
+    moves.reverse();
+    moves
+}
@@ -0,0 +1,202 @@
+use crate::{Color, Game, Tube};
+
+/// Which moves to skip as never helping. Rules that could make the solver
+/// miss a shortest solution are off by default.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pruning {
+    /// Skip pouring a tube that holds a single color into an empty tube of
+    /// the same capacity. That only swaps the two tubes, which gives back
+    /// the same game, so no solution is lost.
+    pub swaps: bool,
+    /// Skip pouring out of a full tube that holds every unit of its color.
+    /// Only applied when every tube has the same capacity, where the one
+    /// pour such a tube allows is a swap. In mixed games parking the color
+    /// in a taller tube can be part of a shortest solution.
+    pub finished: bool,
+    /// Skip pours that leave part of the top block behind because the
+    /// target has too little room. Splitting a block is sometimes needed,
+    /// so this can make solutions longer or miss them entirely, and the
+    /// solver no longer claims its solutions are shortest.
+    pub whole_blocks: bool,
+}
+
+impl Default for Pruning {
+    fn default() -> Self {
+        Pruning {
+            swaps: true,
+            finished: true,
+            whole_blocks: false,
+        }
+    }
+}
+
+impl Pruning {
+    /// Keeps every legal move.
+    pub fn none() -> Self {
+        Pruning {
+            swaps: false,
+            finished: false,
+            whole_blocks: false,
+        }
+    }
+
+    /// Whether every shortest solution survives the rules.
+    pub fn is_optimal(&self) -> bool {
+        !self.whole_blocks
+    }
+
+    /// The rule that cuts the legal pour from tube `a` into tube `b`, if any.
+    pub(crate) fn cut(&self, game: &Game, a: usize, b: usize) -> Option<Rule> {
+        let (from, into) = (&game.state[a], &game.state[b]);
+        if self.finished && game.capacity().is_some() && is_finished(game, from) {
+            return Some(Rule::Finished);
+        }
+        if self.swaps && is_single(from) && is_empty(into) && from.capacity() == into.capacity() {
+            return Some(Rule::Swaps);
+        }
+        if self.whole_blocks && from.top_block() > into.room() {
+            return Some(Rule::WholeBlocks);
+        }
+        None
+    }
+}
+
+pub(crate) enum Rule {
+    Swaps,
+    Finished,
+    WholeBlocks,
+}
+
+/// How many moves each [`Pruning`] rule skipped.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Pruned {
+    pub swaps: usize,
+    pub finished: usize,
+    pub whole_blocks: usize,
+}
+
+impl Pruned {
+    pub(crate) fn count(&mut self, rule: Rule) {
+        match rule {
+            Rule::Swaps => self.swaps += 1,
+            Rule::Finished => self.finished += 1,
+            Rule::WholeBlocks => self.whole_blocks += 1,
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.swaps + self.finished + self.whole_blocks
+    }
+}
+
+/// Whether the tube holds liquid of one color only.
+fn is_single(tube: &Tube) -> bool {
+    let mut liquid = tube.colors().iter().filter(|color| color.is_value());
+    match liquid.next() {
+        Some(first) => liquid.all(|color| color == first),
+        None => false,
+    }
+}
+
+fn is_empty(tube: &Tube) -> bool {
+    tube.colors().iter().all(Color::is_empty)
+}
+
+/// Whether the tube is full of one color and no other tube holds any of it.
+fn is_finished(game: &Game, tube: &Tube) -> bool {
+    let color = tube.colors()[0];
+    color.is_value()
+        && tube.is_uniform()
+        && game
+            .state
+            .iter()
+            .filter(|other| other.id() != tube.id())
+            .all(|other| !other.colors().contains(&color))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn children(game: &Game, pruning: Pruning) -> (Vec<Game>, Pruned) {
+        let mut pruned = Pruned::default();
+        let games = game
+            .moves(&pruning, &mut pruned)
+            .into_iter()
+            .map(|(_, game)| game)
+            .collect();
+        (games, pruned)
+    }
+
+    fn only(rule: impl FnOnce(&mut Pruning)) -> Pruning {
+        let mut pruning = Pruning::none();
+        rule(&mut pruning);
+        pruning
+    }
+
+    #[test]
+    fn no_pruning_keeps_every_move() {
+        let game = Game::new(vec![[0, 0, 1, 1], [1, 1, 2, 2], [0, 0, 2, 2], [0; 4]]);
+        let (games, pruned) = children(&game, Pruning::none());
+        assert_eq!(games.len(), 3);
+        assert_eq!(pruned, Pruned::default());
+    }
+
+    #[test]
+    fn swaps() {
+        // Pouring the lone 1s into the empty tube gives back the same game
+        let mut game = Game::new(vec![[0, 0, 1, 1], [1, 1, 2, 2], [0, 0, 2, 2], [0; 4]]);
+        game.sort();
+        let (all, _) = children(&game, Pruning::none());
+        assert!(all.contains(&game));
+        let (games, pruned) = children(&game, only(|pruning| pruning.swaps = true));
+        assert!(!games.contains(&game));
+        assert_eq!(games.len(), all.len() - 1);
+        assert_eq!(pruned.swaps, 2);
+    }
+
+    #[test]
+    fn swaps_keep_other_capacities() {
+        let game = Game::mixed(vec![
+            vec![0, 1, 1],
+            vec![2, 2, 1, 2],
+            vec![0; 4],
+            vec![0; 2],
+        ])
+        .unwrap();
+        let (_, pruned) = children(&game, only(|pruning| pruning.swaps = true));
+        assert_eq!(pruned.swaps, 0);
+    }
+
+    #[test]
+    fn finished() {
+        let game = Game::new(vec![[1; 4], [2, 3, 2, 3], [3, 2, 3, 2], [0; 4]]);
+        let (games, pruned) = children(&game, only(|pruning| pruning.finished = true));
+        assert_eq!(pruned.finished, 1);
+        let (all, _) = children(&game, Pruning::none());
+        assert_eq!(games.len(), all.len() - 1);
+    }
+
+    #[test]
+    fn finished_only_with_equal_tubes() {
+        // The 1s could be parked in the taller tube
+        let game = Game::mixed(vec![vec![1; 4], vec![0; 6], vec![2; 6]]).unwrap();
+        let (_, pruned) = children(&game, only(|pruning| pruning.finished = true));
+        assert_eq!(pruned.finished, 0);
+        let game = Game::new(vec![[0, 1, 1, 1], [1, 2, 2, 2], [0, 0, 0, 2], [0; 4]]);
+        let (_, pruned) = children(&game, only(|pruning| pruning.finished = true));
+        assert_eq!(pruned.finished, 0);
+    }
+
+    #[test]
+    fn whole_blocks() {
+        // Only one of the three 1s on top of the first tube fits in the second
+        let game = Game::new(vec![[0, 1, 1, 1], [0, 1, 2, 2], [0, 0, 2, 2], [0; 4]]);
+        let (games, pruned) = children(&game, only(|pruning| pruning.whole_blocks = true));
+        assert_eq!(pruned.whole_blocks, 1);
+        let (all, _) = children(&game, Pruning::none());
+        assert_eq!(games.len(), all.len() - 1);
+        assert!(!only(|pruning| pruning.whole_blocks = true).is_optimal());
+        assert!(Pruning::default().is_optimal());
+    }
+}
@@ -0,0 +1,312 @@
+use std::{fmt, str::FromStr};
+
+use crate::{Game, Palette, ParseError, ParseErrorKind, Tube, MAX_CAPACITY};
+
+/// A game with the metadata it was written down with. Puzzles are kept in a
+/// plain text format, one tube per line:
+///
+/// ```text
+/// # Anything after a hash is a comment
+/// level: 3145
+/// capacity: 4
+///
+/// 4 3 2 1
+/// 1 7 6 5
+/// 9 8
+/// . . . .
+/// ```
+///
+/// Each tube lists its slots top to bottom, separated by spaces, with a color
+/// number for liquid and `.` or `0` for an empty slot. Tubes are numbered
+/// from 1 in the order they are listed, which gives their ids.
+///
+/// Lines of the form `key: value` before the first tube give metadata. The
+/// `level` is just kept with the puzzle. The `capacity` sets how many units
+/// every tube holds, so that a tube can list only its bottom slots and the
+/// rest are empty: above, `9 8` is `. . 9 8`. Without a capacity each tube
+/// holds as many units as it lists.
+///
+/// Colors can also be written by name, read with a [`Palette`] given to
+/// [`Puzzle::parse_with`] and written with [`Puzzle::render`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Puzzle {
+    pub game: Game,
+    pub level: Option<usize>,
+    /// The capacity given for every tube, if any.
+    pub capacity: Option<usize>,
+}
+
+impl Puzzle {
+    /// Reads a puzzle in the text format described on [`Puzzle`], with
+    /// colors only as numbers.
+    pub fn parse(text: &str) -> Result<Puzzle, ParseError> {
+        Puzzle::parse_with(text, &Palette::new())
+    }
+
+    /// Reads a puzzle like [`Puzzle::parse`], also taking the names in
+    /// `palette` for colors.
+    pub fn parse_with(text: &str, palette: &Palette) -> Result<Puzzle, ParseError> {
+        let mut level = None;
+        let mut capacity = None;
+        let mut tubes = vec![];
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let error = |column: usize, kind| ParseError {
+                line: line_number,
+                column: column + 1,
+                kind,
+            };
+            let content = line.split('#').next().unwrap_or_default();
+            if let Some((key, value)) = content.split_once(':') {
+                let key_column = column(line, key.trim_start());
+                let value_column = column(line, value.trim_start());
+                if !tubes.is_empty() {
+                    return Err(error(key_column, ParseErrorKind::LateMetadata));
+                }
+                let (key, value) = (key.trim(), value.trim());
+                let number: usize = value
+                    .parse()
+                    .map_err(|_| error(value_column, ParseErrorKind::BadValue(value.into())))?;
+                let slot = match key {
+                    "level" => &mut level,
+                    "capacity" if (1..=MAX_CAPACITY).contains(&number) => &mut capacity,
+                    "capacity" => {
+                        return Err(error(value_column, ParseErrorKind::BadValue(value.into())))
+                    }
+                    _ => return Err(error(key_column, ParseErrorKind::UnknownKey(key.into()))),
+                };
+                if slot.replace(number).is_some() {
+                    return Err(error(key_column, ParseErrorKind::DuplicateKey(key.into())));
+                }
+                continue;
+            }
+
+            let mut slots = vec![];
+            for word in content.split_whitespace() {
+                let word_column = column(line, word);
+                let most = capacity.unwrap_or(MAX_CAPACITY);
+                if slots.len() == most {
+                    let slots = content.split_whitespace().count();
+                    return Err(error(
+                        word_column,
+                        ParseErrorKind::TooManySlots {
+                            slots,
+                            capacity: most,
+                        },
+                    ));
+                }
+                let color = match (word, palette.number(word)) {
+                    (".", _) => 0,
+                    (_, Some(number)) => number,
+                    _ => word
+                        .parse()
+                        .map_err(|_| error(word_column, ParseErrorKind::BadColor(word.into())))?,
+                };
+                slots.push(color);
+            }
+            if slots.is_empty() {
+                continue;
+            }
+            if let Some(capacity) = capacity {
+                slots.splice(0..0, vec![0; capacity - slots.len()]);
+            }
+            tubes.push(slots);
+        }
+        Ok(Puzzle {
+            game: Game::mixed(tubes).expect("Each tube lists 1 to MAX_CAPACITY slots"),
+            level,
+            capacity,
+        })
+    }
+
+    /// Writes the puzzle like printing it does, but with each color by its
+    /// name in `palette`, so that [`Puzzle::parse_with`] reads it back.
+    pub fn render(&self, palette: &Palette) -> String {
+        let mut text = String::new();
+        if let Some(level) = self.level {
+            text += &format!("level: {level}\n");
+        }
+        if let Some(capacity) = self.capacity {
+            text += &format!("capacity: {capacity}\n");
+        }
+        let mut tubes: Vec<&Tube> = self.game.state.iter().collect();
+        tubes.sort_by_key(|tube| tube.id());
+        let width = tubes
+            .iter()
+            .flat_map(|tube| tube.colors())
+            .map(|&color| palette.write(color).chars().count())
+            .max()
+            .unwrap_or(1);
+        for tube in tubes {
+            let slots: Vec<String> = tube
+                .colors()
+                .iter()
+                .map(|&color| format!("{:>width$}", palette.write(color)))
+                .collect();
+            text += &slots.join(" ");
+            text += "\n";
+        }
+        text
+    }
+}
+
+/// The number of characters in `line` before `part`, a slice of it.
+fn column(line: &str, part: &str) -> usize {
+    let offset = part.as_ptr() as usize - line.as_ptr() as usize;
+    line[..offset].chars().count()
+}
+
+impl FromStr for Puzzle {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Puzzle::parse(text)
+    }
+}
+
+impl From<Game> for Puzzle {
+    fn from(game: Game) -> Self {
+        Puzzle {
+            game,
+            level: None,
+            capacity: None,
+        }
+    }
+}
+
+/// Writes the puzzle in the text format, every slot of every tube in order
+/// of tube id, so that parsing it gives back the same game. A game whose
+/// tubes were reordered, as solving does, comes back in order of id.
+impl fmt::Display for Puzzle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(&Palette::new()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses() {
+        let text = "\
+# The first level
+level: 1
+capacity: 4
+
+1 2 1 2   # Mixed up
+2 1 2 1
+.
+";
+        let puzzle = Puzzle::parse(text).unwrap();
+        assert_eq!(puzzle.level, Some(1));
+        assert_eq!(puzzle.capacity, Some(4));
+        assert_eq!(
+            puzzle.game,
+            Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]])
+        );
+    }
+
+    #[test]
+    fn tubes_without_capacity_hold_what_they_list() {
+        let puzzle: Puzzle = "1 2 1 2\n2 1 2 1 . .\n0 0".parse().unwrap();
+        assert_eq!(
+            puzzle.game,
+            Game::mixed(vec![vec![1, 2, 1, 2], vec![2, 1, 2, 1, 0, 0], vec![0, 0]]).unwrap()
+        );
+        assert_eq!(puzzle.capacity, None);
+    }
+
+    #[test]
+    fn round_trips() {
+        let games = [
+            Game::new(vec![[1, 2, 3, 4], [5, 1, 6, 3], [0; 4], [0, 0, 12, 255]]),
+            Game::mixed(vec![vec![1, 2, 1], vec![2, 1, 2, 1, 2], vec![0; 8]]).unwrap(),
+        ];
+        for game in games {
+            let puzzle = Puzzle {
+                game,
+                level: Some(7),
+                capacity: None,
+            };
+            assert_eq!(Puzzle::parse(&puzzle.to_string()), Ok(puzzle.clone()));
+        }
+        let mut game = Game::new(vec![[0, 0, 2, 2], [1, 1, 1, 1], [0, 0, 2, 2]]);
+        game.sort();
+        let mut parsed = Puzzle::parse(&Puzzle::from(game.clone()).to_string())
+            .unwrap()
+            .game;
+        parsed.sort();
+        assert_eq!(parsed, game);
+        let puzzle = Puzzle::parse("capacity: 4\n1 1\n1 1").unwrap();
+        assert_eq!(Puzzle::parse(&puzzle.to_string()), Ok(puzzle));
+    }
+
+    #[test]
+    fn names_colors_with_a_palette() {
+        let palette = Palette::standard();
+        let puzzle = Puzzle::parse_with("capacity: 4\nR lime R 8\nl red L 1\n.", &palette).unwrap();
+        assert_eq!(
+            puzzle.game,
+            Game::new(vec![[1, 8, 1, 8], [8, 1, 8, 1], [0; 4]])
+        );
+        let text = puzzle.render(&palette);
+        assert_eq!(text, "capacity: 4\nR L R L\nL R L R\n. . . .\n");
+        assert_eq!(Puzzle::parse_with(&text, &palette), Ok(puzzle.clone()));
+        // Without the palette the names are not colors
+        assert_eq!(
+            Puzzle::parse(&text).unwrap_err().kind,
+            ParseErrorKind::BadColor("R".into())
+        );
+        // Colors without a name are still written as numbers
+        let palette = Palette::new().with(1, ["pink"]);
+        assert_eq!(
+            puzzle.render(&palette),
+            "capacity: 4\npink    8 pink    8\n   8 pink    8 pink\n   .    .    .    .\n"
+        );
+    }
+
+    #[test]
+    fn reports_where_errors_are() {
+        let error = |text: &str| {
+            let error = Puzzle::parse(text).unwrap_err();
+            (error.line, error.column, error.kind)
+        };
+        assert_eq!(
+            error("1 2\n1 x 2"),
+            (2, 3, ParseErrorKind::BadColor("x".into()))
+        );
+        assert_eq!(
+            error("1 256"),
+            (1, 3, ParseErrorKind::BadColor("256".into()))
+        );
+        assert_eq!(
+            error("capacity: 2\n\n  1 2 3"),
+            (
+                3,
+                7,
+                ParseErrorKind::TooManySlots {
+                    slots: 3,
+                    capacity: 2
+                }
+            )
+        );
+        assert_eq!(error("1 2\nlevel: 3"), (2, 1, ParseErrorKind::LateMetadata));
+        assert_eq!(
+            error("size: 3"),
+            (1, 1, ParseErrorKind::UnknownKey("size".into()))
+        );
+        assert_eq!(
+            error("level: 3\n level: 4"),
+            (2, 2, ParseErrorKind::DuplicateKey("level".into()))
+        );
+        assert_eq!(
+            error("capacity:  9"),
+            (1, 12, ParseErrorKind::BadValue("9".into()))
+        );
+        assert_eq!(
+            Puzzle::parse("1 x").unwrap_err().to_string(),
+            "Line 1, column 3: \"x\" is not a color"
+        );
+    }
+}
@@ -0,0 +1,373 @@
+use std::{
+    collections::HashSet,
+    fmt, io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{Game, Progress, Pruned, Pruning, Solution, SolverObserver};
+
+/// A flag for stopping a solve from another thread. Clones share the flag.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// How much work a solve may do before giving up. Every limit is off by
+/// default.
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    /// The most games to expand.
+    pub max_states: Option<usize>,
+    /// The most moves a solution may take.
+    pub max_depth: Option<usize>,
+    /// When to stop searching.
+    pub deadline: Option<Instant>,
+    pub cancel: Option<CancelToken>,
+}
+
+/// The limit that stopped a solve.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    States,
+    Depth,
+    Deadline,
+    Cancelled,
+    /// The files of a search kept on disk could not be written or read.
+    Io(io::ErrorKind),
+}
+
+impl From<io::Error> for Limit {
+    fn from(error: io::Error) -> Self {
+        Limit::Io(error.kind())
+    }
+}
+
+/// How much work a solve did.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Games whose moves were generated.
+    pub expanded: usize,
+    /// Games the search remembered having seen.
+    pub visited: usize,
+    /// The most games waiting to be expanded at once.
+    pub peak_frontier: usize,
+    /// The most moves made to reach an expanded game.
+    pub depth: usize,
+    pub elapsed: Duration,
+    /// The moves skipped by each pruning rule.
+    pub pruned: Pruned,
+}
+
+/// How a solve ended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SolveOutcome {
+    /// Every solution found takes `depth` moves. They are the shortest
+    /// possible when `optimal` is set.
+    Solved {
+        solutions: Vec<Solution>,
+        depth: usize,
+        optimal: bool,
+        stats: Stats,
+    },
+    /// Every game reachable from the start was checked and none is solved.
+    Unsolvable { stats: Stats },
+    /// A limit was hit before the search could tell either way.
+    Aborted { limit: Limit, stats: Stats },
+}
+
+impl SolveOutcome {
+    pub fn stats(&self) -> &Stats {
+        match self {
+            SolveOutcome::Solved { stats, .. }
+            | SolveOutcome::Unsolvable { stats }
+            | SolveOutcome::Aborted { stats, .. } => stats,
+        }
+    }
+
+    pub fn into_solutions(self) -> Option<Vec<Solution>> {
+        match self {
+            SolveOutcome::Solved { solutions, .. } => Some(solutions),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SolveOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolveOutcome::Solved {
+                solutions,
+                depth,
+                optimal,
+                ..
+            } => {
+                write!(
+                    f,
+                    "Done! {} Solution(s) Found with {depth} moves",
+                    solutions.len()
+                )?;
+                if !optimal {
+                    write!(f, ", shorter solutions may exist")?;
+                }
+            }
+            SolveOutcome::Unsolvable { .. } => write!(f, "Game is unsolvable")?,
+            SolveOutcome::Aborted { limit, .. } => match limit {
+                Limit::States => write!(f, "Too many games to check")?,
+                Limit::Depth => write!(f, "No solution within the move limit")?,
+                Limit::Deadline => write!(f, "Ran out of time")?,
+                Limit::Cancelled => write!(f, "Cancelled")?,
+                Limit::Io(kind) => write!(f, "Could not use the disk: {kind}")?,
+            },
+        }
+        let stats = self.stats();
+        write!(
+            f,
+            " (checked {} games {} moves deep in {:?})",
+            stats.expanded, stats.depth, stats.elapsed
+        )
+    }
+}
+
+/// How a search gets from a game to the games one move away.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Expansion {
+    pub(crate) pruning: Pruning,
+    /// Whether to rename the colors of every game reached, see
+    /// [`Game::canonicalize`].
+    pub(crate) symmetry: bool,
+}
+
+impl Default for Expansion {
+    fn default() -> Self {
+        Expansion {
+            pruning: Pruning::none(),
+            symmetry: false,
+        }
+    }
+}
+
+impl Expansion {
+    /// The games one move away from `game`, with the move that reaches each,
+    /// counting the moves pruned away in `pruned`.
+    pub(crate) fn children(&self, game: &Game, pruned: &mut Pruned) -> Vec<((usize, usize), Game)> {
+        let mut children = game.moves(&self.pruning, pruned);
+        if self.symmetry {
+            let mut seen = HashSet::with_capacity(children.len());
+            children.retain_mut(|(_, child)| {
+                child.canonicalize();
+                seen.insert(child.clone())
+            });
+        }
+        children
+    }
+}
+
+/// Counts the work a search does, checks it against the limits and passes
+/// progress on to the observer. Searches also get the games one move away
+/// from here, so that they all treat them the same.
+pub(crate) struct Budget {
+    limits: Limits,
+    start: Instant,
+    stats: Stats,
+    /// Whether any game was left out for being too deep.
+    cut_off: bool,
+    pub(crate) observer: Option<Box<dyn SolverObserver>>,
+    pub(crate) expansion: Expansion,
+}
+
+impl Budget {
+    pub(crate) fn new(limits: &Limits) -> Self {
+        Budget {
+            limits: limits.clone(),
+            start: Instant::now(),
+            stats: Stats::default(),
+            cut_off: false,
+            observer: None,
+            expansion: Expansion::default(),
+        }
+    }
+
+    /// The games one move away from `game`, with the move that reaches each.
+    pub(crate) fn children(&mut self, game: &Game) -> Vec<((usize, usize), Game)> {
+        self.expansion.children(game, &mut self.stats.pruned)
+    }
+
+    /// Adds up moves pruned away from the budget, such as on another thread.
+    pub(crate) fn pruned(&mut self, pruned: &Pruned) {
+        let total = &mut self.stats.pruned;
+        total.swaps += pruned.swaps;
+        total.finished += pruned.finished;
+        total.whole_blocks += pruned.whole_blocks;
+    }
+
+    /// Counts a game `depth` moves deep as expanded, unless a limit says to
+    /// stop first.
+    pub(crate) fn expand(&mut self, depth: usize) -> Result<(), Limit> {
+        if self
+            .limits
+            .max_states
+            .is_some_and(|max| self.stats.expanded >= max)
+        {
+            return Err(Limit::States);
+        }
+        if self
+            .limits
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Err(Limit::Deadline);
+        }
+        if self
+            .limits
+            .cancel
+            .as_ref()
+            .is_some_and(CancelToken::is_cancelled)
+        {
+            return Err(Limit::Cancelled);
+        }
+        self.stats.expanded += 1;
+        self.stats.depth = self.stats.depth.max(depth);
+        Ok(())
+    }
+
+    /// Whether games `depth` moves deep are past the depth limit, remembering
+    /// that the search left some out.
+    pub(crate) fn too_deep(&mut self, depth: usize) -> bool {
+        let too_deep = self.limits.max_depth.is_some_and(|max| depth > max);
+        self.cut_off |= too_deep;
+        too_deep
+    }
+
+    /// Ends a search that ran out of games. The game is only unsolvable if
+    /// nothing was left out for being too deep.
+    pub(crate) fn exhausted<T>(&self) -> Result<Option<T>, Limit> {
+        if self.cut_off {
+            Err(Limit::Depth)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Records how many games are waiting to be expanded and how many the
+    /// search remembers.
+    pub(crate) fn sizes(&mut self, frontier: usize, visited: usize) {
+        self.stats.peak_frontier = self.stats.peak_frontier.max(frontier);
+        self.stats.visited = self.stats.visited.max(visited);
+    }
+
+    /// Tells the observer that a layer or iteration `depth` moves deep is
+    /// done.
+    pub(crate) fn progress(&mut self, depth: usize, frontier: usize, visited: usize) {
+        self.sizes(frontier, visited);
+        if let Some(observer) = self.observer.as_mut() {
+            observer.on_progress(&Progress {
+                depth,
+                frontier,
+                visited,
+                expanded: self.stats.expanded,
+                elapsed: self.start.elapsed(),
+            });
+        }
+    }
+
+    pub(crate) fn expanded(&self) -> usize {
+        self.stats.expanded
+    }
+
+    pub(crate) fn found(&mut self, solution: &Solution) {
+        if let Some(observer) = self.observer.as_mut() {
+            observer.on_solution(solution);
+        }
+    }
+
+    pub(crate) fn stats(&mut self) -> Stats {
+        self.stats.elapsed = self.start.elapsed();
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_limits() {
+        let mut budget = Budget::new(&Limits::default());
+        for depth in 0..1000 {
+            assert!(budget.expand(depth).is_ok());
+            assert!(!budget.too_deep(depth));
+        }
+        assert_eq!(budget.exhausted::<()>(), Ok(None));
+        assert_eq!(budget.stats().expanded, 1000);
+        assert_eq!(budget.stats().depth, 999);
+    }
+
+    #[test]
+    fn sizes() {
+        let mut budget = Budget::new(&Limits::default());
+        budget.sizes(10, 5);
+        budget.sizes(3, 8);
+        assert_eq!(budget.stats().peak_frontier, 10);
+        assert_eq!(budget.stats().visited, 8);
+    }
+
+    #[test]
+    fn state_limit() {
+        let mut budget = Budget::new(&Limits {
+            max_states: Some(2),
+            ..Limits::default()
+        });
+        assert!(budget.expand(0).is_ok());
+        assert!(budget.expand(1).is_ok());
+        assert_eq!(budget.expand(1), Err(Limit::States));
+    }
+
+    #[test]
+    fn depth_limit() {
+        let mut budget = Budget::new(&Limits {
+            max_depth: Some(3),
+            ..Limits::default()
+        });
+        assert!(!budget.too_deep(3));
+        assert_eq!(budget.exhausted::<()>(), Ok(None));
+        assert!(budget.too_deep(4));
+        assert_eq!(budget.exhausted::<()>(), Err(Limit::Depth));
+    }
+
+    #[test]
+    fn deadline() {
+        let mut budget = Budget::new(&Limits {
+            deadline: Some(Instant::now()),
+            ..Limits::default()
+        });
+        assert_eq!(budget.expand(0), Err(Limit::Deadline));
+    }
+
+    #[test]
+    fn cancel() {
+        let cancel = CancelToken::new();
+        let mut budget = Budget::new(&Limits {
+            cancel: Some(cancel.clone()),
+            ..Limits::default()
+        });
+        assert!(budget.expand(0).is_ok());
+        std::thread::spawn(move || cancel.cancel()).join().unwrap();
+        assert_eq!(budget.expand(1), Err(Limit::Cancelled));
+    }
+}
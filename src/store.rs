@@ -0,0 +1,206 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::BuildHasher,
+};
+
+use crate::{Game, PackedGame, Solution};
+
+/// Every game a search has seen, each stored with a link to the game it was
+/// reached from. Solutions are rebuilt by walking the links back to the
+/// start, so a stored game costs the same however deep it is. Games are kept
+/// packed, see [`PackedGame`].
+#[derive(Debug, Default)]
+pub(crate) struct Store {
+    nodes: Vec<Node>,
+    /// The last game stored with each hash. Games sharing a hash are chained
+    /// through [`Node::next`].
+    index: HashMap<u64, usize>,
+    hasher: RandomState,
+}
+
+#[derive(Debug)]
+struct Node {
+    game: PackedGame,
+    /// The game this one was reached from and the move that got here.
+    parent: Option<(usize, (usize, usize))>,
+    depth: usize,
+    /// The game stored before this one with the same hash.
+    next: Option<usize>,
+}
+
+impl Store {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// A store hashing games the same way as every other store made with
+    /// `hasher`.
+    pub(crate) fn with_hasher(hasher: RandomState) -> Self {
+        Store {
+            hasher,
+            ..Self::default()
+        }
+    }
+
+    /// Stores `game`, reached by `step` from the game at `parent`, or as the
+    /// start if there is no parent. Returns its index, or `None` if the game
+    /// was already stored.
+    pub(crate) fn insert(
+        &mut self,
+        game: &Game,
+        parent: Option<(usize, (usize, usize))>,
+    ) -> Option<usize> {
+        let game = game.pack();
+        let hash = self.hasher.hash_one(&game);
+        if self.find_hashed(hash, &game).is_some() {
+            return None;
+        }
+        let depth = parent.map_or(0, |(parent, _)| self.nodes[parent].depth + 1);
+        Some(self.push_hashed(hash, game, parent, depth))
+    }
+
+    /// Stores a game already packed and hashed with this store's hasher,
+    /// `depth` moves deep. The parent is not looked up, so it can be an index
+    /// into some other store. Returns its index, or `None` if the game was
+    /// already stored.
+    pub(crate) fn insert_packed(
+        &mut self,
+        hash: u64,
+        game: PackedGame,
+        parent: Option<(usize, (usize, usize))>,
+        depth: usize,
+    ) -> Option<usize> {
+        if self.find_hashed(hash, &game).is_some() {
+            return None;
+        }
+        Some(self.push_hashed(hash, game, parent, depth))
+    }
+
+    /// Stores `game` even if it was stored before, for when it has been
+    /// reached in fewer moves. Equal games can have their tube ids in a
+    /// different order, so the old copy is kept for the games reached from
+    /// it, while [`find`](Store::find) returns the new one.
+    pub(crate) fn push(&mut self, game: &Game, parent: Option<(usize, (usize, usize))>) -> usize {
+        let game = game.pack();
+        let hash = self.hasher.hash_one(&game);
+        let depth = parent.map_or(0, |(parent, _)| self.nodes[parent].depth + 1);
+        self.push_hashed(hash, game, parent, depth)
+    }
+
+    fn push_hashed(
+        &mut self,
+        hash: u64,
+        game: PackedGame,
+        parent: Option<(usize, (usize, usize))>,
+        depth: usize,
+    ) -> usize {
+        let index = self.nodes.len();
+        let next = self.index.insert(hash, index);
+        self.nodes.push(Node {
+            game,
+            parent,
+            depth,
+            next,
+        });
+        index
+    }
+
+    pub(crate) fn find(&self, game: &Game) -> Option<usize> {
+        let game = game.pack();
+        self.find_hashed(self.hasher.hash_one(&game), &game)
+    }
+
+    /// Finds a game already packed and hashed with this store's hasher.
+    pub(crate) fn find_hashed(&self, hash: u64, game: &PackedGame) -> Option<usize> {
+        let mut index = self.index.get(&hash).copied();
+        while let Some(i) = index {
+            if self.nodes[i].game == *game {
+                return Some(i);
+            }
+            index = self.nodes[i].next;
+        }
+        None
+    }
+
+    /// Whether the game at `index` is the last copy of it stored, rather
+    /// than one reached again in fewer moves since.
+    pub(crate) fn is_latest(&self, index: usize) -> bool {
+        let game = &self.nodes[index].game;
+        self.find_hashed(self.hasher.hash_one(game), game) == Some(index)
+    }
+
+    pub(crate) fn game(&self, index: usize) -> Game {
+        self.nodes[index].game.unpack()
+    }
+
+    /// The number of moves made to reach the game at `index`.
+    pub(crate) fn depth(&self, index: usize) -> usize {
+        self.nodes[index].depth
+    }
+
+    /// The game the one at `index` was reached from, and the move made.
+    pub(crate) fn parent(&self, index: usize) -> Option<(usize, (usize, usize))> {
+        self.nodes[index].parent
+    }
+
+    /// The moves from the start to the game at `index`.
+    pub(crate) fn path(&self, mut index: usize) -> Solution {
+        let mut moves = Vec::with_capacity(self.depth(index));
+        while let Some((parent, step)) = self.nodes[index].parent {
+            moves.push(step);
+            index = parent;
+        }
+        moves.reverse();
+        moves
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.nodes.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Pruned, Pruning};
+
+    #[test]
+    fn stores_each_game_once() {
+        let mut store = Store::new();
+        let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]);
+        assert_eq!(store.insert(&game, None), Some(0));
+        assert_eq!(store.insert(&game, None), None);
+        assert_eq!(store.find(&game), Some(0));
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn rebuilds_paths() {
+        let mut store = Store::new();
+        let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]);
+        let start = store.insert(&game, None).unwrap();
+        let mut games = game
+            .moves(&Pruning::none(), &mut Pruned::default())
+            .into_iter();
+        let (step, child) = games.next().unwrap();
+        let (next_step, grandchild) = child
+            .moves(&Pruning::none(), &mut Pruned::default())
+            .into_iter()
+            .find(|(_, grandchild)| *grandchild != game)
+            .unwrap();
+        let child = store.insert(&child, Some((start, step))).unwrap();
+        let grandchild = store.insert(&grandchild, Some((child, next_step))).unwrap();
+        assert_eq!(store.depth(grandchild), 2);
+        assert_eq!(store.path(grandchild), vec![step, next_step]);
+        assert_eq!(store.path(start), vec![]);
+
+        let game = store.game(grandchild);
+        let shortcut = store.push(&game, Some((start, (9, 9))));
+        assert_eq!(store.find(&game), Some(shortcut));
+        assert!(store.is_latest(shortcut));
+        assert!(!store.is_latest(grandchild));
+        assert_eq!(store.depth(shortcut), 1);
+        assert_eq!(store.path(shortcut), vec![(9, 9)]);
+        assert_eq!(store.path(grandchild), vec![step, next_step]);
+    }
+}
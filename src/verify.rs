@@ -0,0 +1,169 @@
+use crate::{Color, Game, Gravity, MoveError, MoveErrorKind};
+
+/// One pour of a solution, with what it moved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pour {
+    /// The id of the tube poured from.
+    pub from: usize,
+    /// The id of the tube poured into.
+    pub to: usize,
+    /// How many units were poured.
+    pub amount: usize,
+    pub color: Color,
+}
+
+impl Pour {
+    /// What each move of `solution` pours, up to the first one that can't
+    /// be made. See [`verify_solution`].
+    pub fn replay(game: &Game, solution: &[(usize, usize)]) -> Vec<Pour> {
+        verify_solution(game, solution).pours
+    }
+}
+
+/// What making the moves of a solution did.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Replay {
+    /// The moves made, up to the first illegal one.
+    pub pours: Vec<Pour>,
+    /// The first move that can't be made, if any.
+    pub error: Option<MoveError>,
+    /// Whether the game is solved after the moves made.
+    pub solved: bool,
+}
+
+impl Replay {
+    /// Every move can be made and the game ends up solved.
+    pub fn is_solution(&self) -> bool {
+        self.error.is_none() && self.solved
+    }
+}
+
+/// Makes the moves of `solution` on `game`, each a pair of tube ids poured
+/// from and into, and stops at the first one that can't be made. The game
+/// is settled first, as the solver does.
+pub fn verify_solution(game: &Game, solution: &[(usize, usize)]) -> Replay {
+    let mut game = game.clone();
+    game.normalize(Gravity::Settle)
+        .expect("Settling never fails");
+    let mut pours = vec![];
+    let mut error = None;
+    for (index, &(from, to)) in solution.iter().enumerate() {
+        match pour(&mut game, from, to) {
+            Ok(pour) => pours.push(pour),
+            Err(kind) => {
+                error = Some(MoveError {
+                    number: index + 1,
+                    from,
+                    to,
+                    kind,
+                });
+                break;
+            }
+        }
+    }
+    Replay {
+        pours,
+        error,
+        solved: game.is_solved(),
+    }
+}
+
+/// Pours tube `from` into tube `to`, by id, or says why it can't.
+fn pour(game: &mut Game, from: usize, to: usize) -> Result<Pour, MoveErrorKind> {
+    let index = |id| game.tube(id).ok_or(MoveErrorKind::UnknownId(id));
+    let (a, b) = (index(from)?, index(to)?);
+    let (source, target) = (game.state[a], game.state[b]);
+    let color = source.top();
+    if a == b {
+        return Err(MoveErrorKind::SameTube);
+    }
+    if color.is_empty() {
+        return Err(MoveErrorKind::SourceEmpty);
+    }
+    if target.room() == 0 {
+        return Err(MoveErrorKind::TargetFull);
+    }
+    if target.top().is_value() && target.top() != color {
+        return Err(MoveErrorKind::ColorMismatch {
+            poured: color,
+            onto: target.top(),
+        });
+    }
+    assert!(game.pour(a, b), "A legal pour has to be made");
+    Ok(Pour {
+        from,
+        to,
+        amount: game.state[a].room() - source.room(),
+        color,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replays_pours() {
+        let game = Game::new(vec![[0, 0, 1, 2], [0, 1, 1, 2], [0, 0, 0, 2]]);
+        assert_eq!(
+            Pour::replay(&game, &[(2, 1)]),
+            [Pour {
+                from: 2,
+                to: 1,
+                amount: 2,
+                color: Color::Full(1)
+            }]
+        );
+    }
+
+    #[test]
+    fn finds_illegal_moves() {
+        let game = Game::new(vec![[0, 0, 1, 2], [1, 2, 1, 2], [0; 4], [0, 0, 2, 2]]);
+        let kind = |solution: &[(usize, usize)]| {
+            verify_solution(&game, solution)
+                .error
+                .map(|error| (error.number, error.kind))
+        };
+        assert_eq!(kind(&[(1, 3)]), None);
+        assert_eq!(
+            kind(&[(1, 3), (5, 1)]),
+            Some((2, MoveErrorKind::UnknownId(5)))
+        );
+        assert_eq!(kind(&[(1, 1)]), Some((1, MoveErrorKind::SameTube)));
+        assert_eq!(kind(&[(3, 1)]), Some((1, MoveErrorKind::SourceEmpty)));
+        assert_eq!(kind(&[(1, 2)]), Some((1, MoveErrorKind::TargetFull)));
+        assert_eq!(
+            kind(&[(1, 4)]),
+            Some((
+                1,
+                MoveErrorKind::ColorMismatch {
+                    poured: Color::Full(1),
+                    onto: Color::Full(2)
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn reports_whether_solved() {
+        let game = Game::new(vec![[1, 2, 1, 2], [2, 1, 2, 1], [0; 4]]);
+        let solution = [(1, 3), (2, 1), (2, 3), (1, 2), (1, 3), (2, 1), (2, 3)];
+        let replay = verify_solution(&game, &solution);
+        assert!(replay.is_solution());
+        assert_eq!(replay.pours.len(), 7);
+
+        let replay = verify_solution(&game, &solution[..6]);
+        assert_eq!(replay.error, None);
+        assert!(!replay.solved);
+
+        // An illegal move stops the replay short of solving the game
+        let mut wrong = solution.to_vec();
+        wrong.insert(3, (3, 3));
+        let replay = verify_solution(&game, &wrong);
+        assert!(!replay.solved);
+        assert_eq!(
+            replay.error.unwrap().to_string(),
+            "Move 4, tube 3 into tube 3: A tube can't pour into itself"
+        );
+    }
+}